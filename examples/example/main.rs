@@ -1,4 +1,5 @@
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use std::collections::HashSet;
 use std::ffi::{CStr, CString};
 use std::mem::ManuallyDrop;
 use std::os::raw::c_char;
@@ -6,7 +7,6 @@ use std::os::raw::c_char;
 use std::os::raw::c_void;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use std::{collections::HashSet, u64};
 
 use anyhow::Result;
 use ash::ext::debug_utils::Instance as DebugUtils;
@@ -32,10 +32,10 @@ const VALIDATION: &[&str] = &["VK_LAYER_KHRONOS_validation"];
 
 const DEVICE_EXTENSIONS: &[&str] = &["VK_KHR_swapchain"];
 
-const VERTEX_SHADER_PASS: &'static str = "examples/example/shaders/spv/vert.spv";
-const FRAGMENT_SHADER_PASS: &'static str = "examples/example/shaders/spv/frag.spv";
+const VERTEX_SHADER_PASS: &str = "examples/example/shaders/spv/vert.spv";
+const FRAGMENT_SHADER_PASS: &str = "examples/example/shaders/spv/frag.spv";
 
-const MODEL_PATH: &'static str = "examples/example/assets/monkey.obj";
+const MODEL_PATH: &str = "examples/example/assets/monkey.obj";
 
 const MAX_FRAMES_IN_FLIGHT: usize = 2;
 
@@ -221,7 +221,7 @@ impl App {
 
             // Get extensions for creating Surface
             let extension_names = enumerate_required_extensions(window.display_handle()?.as_raw())?;
-            let mut extension_names = extension_names.iter().map(|name| *name).collect::<Vec<_>>();
+            let mut extension_names = extension_names.to_vec();
             if ENABLE_VALIDATION_LAYERS {
                 extension_names.push(ash::ext::debug_utils::NAME.as_ptr());
             }
@@ -457,19 +457,18 @@ impl App {
                     .unwrap()
             };
 
-            let format = formats
+            let format = *formats
                 .iter()
                 .find(|f| {
                     f.format == vk::Format::B8G8R8A8_UNORM || f.format == vk::Format::R8G8B8A8_UNORM
                 })
-                .unwrap_or(&formats[0])
-                .clone();
+                .unwrap_or(&formats[0]);
             let present_mode = present_modes
                 .into_iter()
                 .find(|&p| p == vk::PresentModeKHR::MAILBOX)
                 .unwrap_or(vk::PresentModeKHR::FIFO);
             let extent = {
-                if capabilities.current_extent.width != u32::max_value() {
+                if capabilities.current_extent.width != u32::MAX {
                     capabilities.current_extent
                 } else {
                     vk::Extent2D {
@@ -785,8 +784,11 @@ impl App {
                 use std::fs::File;
                 use std::io::Read;
 
-                let spv_file = File::open(&Path::new(VERTEX_SHADER_PASS))?;
-                let bytes_code: Vec<u8> = spv_file.bytes().filter_map(|byte| byte.ok()).collect();
+                let spv_file = File::open(Path::new(VERTEX_SHADER_PASS))?;
+                let bytes_code: Vec<u8> = std::io::BufReader::new(spv_file)
+                    .bytes()
+                    .filter_map(|byte| byte.ok())
+                    .collect();
 
                 let shader_module_create_info = vk::ShaderModuleCreateInfo {
                     code_size: bytes_code.len(),
@@ -799,8 +801,11 @@ impl App {
                 use std::fs::File;
                 use std::io::Read;
 
-                let spv_file = File::open(&Path::new(FRAGMENT_SHADER_PASS))?;
-                let bytes_code: Vec<u8> = spv_file.bytes().filter_map(|byte| byte.ok()).collect();
+                let spv_file = File::open(Path::new(FRAGMENT_SHADER_PASS))?;
+                let bytes_code: Vec<u8> = std::io::BufReader::new(spv_file)
+                    .bytes()
+                    .filter_map(|byte| byte.ok())
+                    .collect();
 
                 let shader_module_create_info = vk::ShaderModuleCreateInfo {
                     code_size: bytes_code.len(),
@@ -1097,23 +1102,23 @@ impl App {
 
         // #### egui ##########################################################################
         // create integration object
-        // Note: ManuallyDrop is required to drop the allocator to shut it down successfully.
-        let egui_integration = ManuallyDrop::new(egui_winit_ash_integration::Integration::new(
-            event_loop,
-            width,
-            height,
-            window.scale_factor(),
-            physical_device_properties.limits.max_image_dimension2_d as usize,
-            egui::FontDefinitions::default(),
-            egui::Style::default(),
-            device.clone(),
-            Arc::clone(&allocator),
-            graphics_queue_index,
-            graphics_queue,
-            swapchain_loader.clone(),
-            swapchain.clone(),
-            format.clone(),
-        ));
+        // Note: ManuallyDrop is required to drop the integration, which destroys its vk objects,
+        // before the device, and to drop the allocator to shut it down successfully.
+        let egui_integration = ManuallyDrop::new(
+            egui_winit_ash_integration::IntegrationDescriptor::new(
+                width,
+                height,
+                window.scale_factor(),
+                device.clone(),
+                Arc::clone(&allocator),
+                graphics_queue_index,
+                graphics_queue,
+            )
+            .swapchain(event_loop, swapchain_loader.clone(), swapchain, format)
+            .max_texture_side(physical_device_properties.limits.max_image_dimension2_d as usize)
+            .frames_in_flight(MAX_FRAMES_IN_FLIGHT)
+            .build()?,
+        );
         // #### egui ##########################################################################
 
         let allocator = ManuallyDrop::new(allocator);
@@ -1181,20 +1186,19 @@ impl App {
         unsafe {
             // Wait Fence
             let fence = self.fences[self.current_frame];
-            self.device.wait_for_fences(&[fence], true, std::u64::MAX)?;
+            self.device.wait_for_fences(&[fence], true, u64::MAX)?;
 
             // Acquire next image
-            let image_index;
-            match self.swapchain_loader.acquire_next_image(
+            let image_index = match self.swapchain_loader.acquire_next_image(
                 self.swapchain,
-                std::u64::MAX,
+                u64::MAX,
                 self.image_available_semaphores[self.current_frame],
                 vk::Fence::null(),
             ) {
-                Ok((index, _is_suboptimal)) => image_index = index as usize,
+                Ok((index, _is_suboptimal)) => index as usize,
                 Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return self.recreate_swapchain(),
                 Err(error) => panic!("Error while acquiring next image: {}", error),
-            }
+            };
 
             // Reset fence
             self.device.reset_fences(&[fence])?;
@@ -1317,7 +1321,8 @@ impl App {
                     .set_visuals(egui::style::Visuals::light()),
             }
 
-            self.egui_integration.begin_frame(&self.window);
+            self.egui_integration.begin_frame(&self.window)?;
+            self.egui_integration.set_frame_index(self.current_frame)?;
             egui::SidePanel::left("my_side_panel").show(&self.egui_integration.context(), |ui| {
                 ui.heading("Hello");
                 ui.label("Hello egui!");
@@ -1391,7 +1396,7 @@ impl App {
                     ui.separator();
                     ui.text_edit_singleline(&mut self.text);
                 });
-            let output = self.egui_integration.end_frame(&mut self.window);
+            let output = self.egui_integration.end_frame(&self.window)?;
             let clipped_meshes = self
                 .egui_integration
                 .context()
@@ -1401,7 +1406,7 @@ impl App {
                 image_index,
                 clipped_meshes,
                 output.textures_delta,
-            );
+            )?;
             // #### egui ##########################################################################
 
             self.device.end_command_buffer(command_buffer)?;
@@ -1425,7 +1430,7 @@ impl App {
                     .image_indices(&[image_index as u32])
                     .wait_semaphores(&[self.render_finished_semaphores[self.current_frame]]),
             ) {
-                Ok(is_suboptimal) if is_suboptimal == true => return self.recreate_swapchain(),
+                Ok(is_suboptimal) if is_suboptimal => return self.recreate_swapchain(),
                 Ok(_is_suboptimal) => (),
                 Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return self.recreate_swapchain(),
                 Err(error) => panic!("Failed to present queue: {}", error),
@@ -1491,19 +1496,18 @@ impl App {
                     .get_physical_device_surface_present_modes(self.physical_device, self.surface)?
             };
 
-            self.format = formats
+            self.format = *formats
                 .iter()
                 .find(|f| {
                     f.format == vk::Format::B8G8R8A8_UNORM || f.format == vk::Format::R8G8B8A8_UNORM
                 })
-                .unwrap_or(&formats[0])
-                .clone();
+                .unwrap_or(&formats[0]);
             let present_mode = present_modes
                 .into_iter()
                 .find(|&p| p == vk::PresentModeKHR::MAILBOX)
                 .unwrap_or(vk::PresentModeKHR::FIFO);
             self.extent = {
-                if capabilities.current_extent.width != u32::max_value() {
+                if capabilities.current_extent.width != u32::MAX {
                     capabilities.current_extent
                 } else {
                     vk::Extent2D {
@@ -1722,9 +1726,9 @@ impl App {
         self.egui_integration.update_swapchain(
             self.width,
             self.height,
-            self.swapchain.clone(),
-            self.format.clone(),
-        );
+            self.swapchain,
+            self.format,
+        )?;
         // #### egui ##########################################################################
 
         Ok(())
@@ -1736,7 +1740,6 @@ impl Drop for App {
             self.device.queue_wait_idle(self.graphics_queue).unwrap();
             self.device.queue_wait_idle(self.present_queue).unwrap();
 
-            self.egui_integration.destroy();
             ManuallyDrop::drop(&mut self.egui_integration);
 
             for i in 0..MAX_FRAMES_IN_FLIGHT {
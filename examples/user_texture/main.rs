@@ -2,6 +2,7 @@ use egui::epaint::Hsva;
 use egui::load::SizedTexture;
 use egui::{ImageSource, Vec2};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use std::collections::HashSet;
 use std::ffi::{CStr, CString};
 use std::mem::ManuallyDrop;
 use std::os::raw::c_char;
@@ -9,7 +10,6 @@ use std::os::raw::c_char;
 use std::os::raw::c_void;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use std::{collections::HashSet, u64};
 
 use anyhow::Result;
 use ash::ext::debug_utils::Instance as DebugUtils;
@@ -35,11 +35,11 @@ const VALIDATION: &[&str] = &["VK_LAYER_KHRONOS_validation"];
 
 const DEVICE_EXTENSIONS: &[&str] = &["VK_KHR_swapchain"];
 
-const VERTEX_SHADER_PASS: &'static str = "examples/user_texture/shaders/spv/vert.spv";
-const FRAGMENT_SHADER_PASS: &'static str = "examples/user_texture/shaders/spv/frag.spv";
+const VERTEX_SHADER_PASS: &str = "examples/user_texture/shaders/spv/vert.spv";
+const FRAGMENT_SHADER_PASS: &str = "examples/user_texture/shaders/spv/frag.spv";
 
-const MODEL_PATH: &'static str = "examples/user_texture/assets/monkey.obj";
-const IMAGE_PATH: &'static str = "examples/user_texture/assets/Mandrill.bmp";
+const MODEL_PATH: &str = "examples/user_texture/assets/monkey.obj";
+const IMAGE_PATH: &str = "examples/user_texture/assets/Mandrill.bmp";
 
 const SCENE_IMAGE_WIDTH: u32 = 640;
 const SCENE_IMAGE_HEIGHT: u32 = 420;
@@ -235,7 +235,7 @@ impl App {
 
             // Get extensions for creating Surface
             let extension_names = enumerate_required_extensions(window.display_handle()?.as_raw())?;
-            let mut extension_names = extension_names.iter().map(|name| *name).collect::<Vec<_>>();
+            let mut extension_names = extension_names.to_vec();
             if ENABLE_VALIDATION_LAYERS {
                 extension_names.push(ash::ext::debug_utils::NAME.as_ptr());
             }
@@ -471,19 +471,18 @@ impl App {
                     .unwrap()
             };
 
-            let format = formats
+            let format = *formats
                 .iter()
                 .find(|f| {
                     f.format == vk::Format::B8G8R8A8_UNORM || f.format == vk::Format::R8G8B8A8_UNORM
                 })
-                .unwrap_or(&formats[0])
-                .clone();
+                .unwrap_or(&formats[0]);
             let present_mode = present_modes
                 .into_iter()
                 .find(|&p| p == vk::PresentModeKHR::MAILBOX)
                 .unwrap_or(vk::PresentModeKHR::FIFO);
             let extent = {
-                if capabilities.current_extent.width != u32::max_value() {
+                if capabilities.current_extent.width != u32::MAX {
                     capabilities.current_extent
                 } else {
                     vk::Extent2D {
@@ -841,8 +840,11 @@ impl App {
                 use std::fs::File;
                 use std::io::Read;
 
-                let spv_file = File::open(&Path::new(VERTEX_SHADER_PASS))?;
-                let bytes_code: Vec<u8> = spv_file.bytes().filter_map(|byte| byte.ok()).collect();
+                let spv_file = File::open(Path::new(VERTEX_SHADER_PASS))?;
+                let bytes_code: Vec<u8> = std::io::BufReader::new(spv_file)
+                    .bytes()
+                    .filter_map(|byte| byte.ok())
+                    .collect();
 
                 let shader_module_create_info = vk::ShaderModuleCreateInfo {
                     code_size: bytes_code.len(),
@@ -855,8 +857,11 @@ impl App {
                 use std::fs::File;
                 use std::io::Read;
 
-                let spv_file = File::open(&Path::new(FRAGMENT_SHADER_PASS))?;
-                let bytes_code: Vec<u8> = spv_file.bytes().filter_map(|byte| byte.ok()).collect();
+                let spv_file = File::open(Path::new(FRAGMENT_SHADER_PASS))?;
+                let bytes_code: Vec<u8> = std::io::BufReader::new(spv_file)
+                    .bytes()
+                    .filter_map(|byte| byte.ok())
+                    .collect();
 
                 let shader_module_create_info = vk::ShaderModuleCreateInfo {
                     code_size: bytes_code.len(),
@@ -1117,7 +1122,7 @@ impl App {
 
         // Load User Texture image
         let (image, image_allocation, image_view) = {
-            let image_object = image::open(&Path::new(IMAGE_PATH))?;
+            let image_object = image::open(Path::new(IMAGE_PATH))?;
             let (image_width, image_height) = (image_object.width(), image_object.height());
             let image_data = image_object.to_rgba8().into_raw();
 
@@ -1383,7 +1388,8 @@ impl App {
 
         // #### egui ##########################################################################
         // create integration object
-        // Note: ManuallyDrop is required to drop the allocator to shut it down successfully.
+        // Note: ManuallyDrop is required to drop the integration, which destroys its vk objects,
+        // before the device, and to drop the allocator to shut it down successfully.
         let mut egui_integration = ManuallyDrop::new(egui_winit_ash_integration::Integration::new(
             event_loop,
             width,
@@ -1397,19 +1403,17 @@ impl App {
             graphics_queue_index,
             graphics_queue,
             swapchain_loader.clone(),
-            swapchain.clone(),
-            format.clone(),
-        ));
+            swapchain,
+            format,
+        )?);
+        egui_integration.set_frames_in_flight(MAX_FRAMES_IN_FLIGHT)?;
 
         // Register user texture
-        let image_texture_id =
-            egui_integration.register_user_texture(image_view.clone(), sampler.clone());
+        let image_texture_id = egui_integration.register_user_texture(image_view, sampler)?;
         let scene_texture_ids = color_image_views
             .iter()
-            .map(|image_view| {
-                egui_integration.register_user_texture(image_view.clone(), sampler.clone())
-            })
-            .collect::<Vec<_>>();
+            .map(|image_view| egui_integration.register_user_texture(*image_view, sampler))
+            .collect::<Result<Vec<_>, _>>()?;
         // #### egui ##########################################################################
 
         let allocator = ManuallyDrop::new(allocator);
@@ -1489,20 +1493,19 @@ impl App {
         unsafe {
             // Wait Fence
             let fence = self.fences[self.current_frame];
-            self.device.wait_for_fences(&[fence], true, std::u64::MAX)?;
+            self.device.wait_for_fences(&[fence], true, u64::MAX)?;
 
             // Acquire next image
-            let image_index;
-            match self.swapchain_loader.acquire_next_image(
+            let image_index = match self.swapchain_loader.acquire_next_image(
                 self.swapchain,
-                std::u64::MAX,
+                u64::MAX,
                 self.image_available_semaphores[self.current_frame],
                 vk::Fence::null(),
             ) {
-                Ok((index, _is_suboptimal)) => image_index = index as usize,
+                Ok((index, _is_suboptimal)) => index as usize,
                 Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return self.recreate_swapchain(),
                 Err(error) => panic!("Error while acquiring next image: {}", error),
-            }
+            };
 
             // Reset fence
             self.device.reset_fences(&[fence])?;
@@ -1704,7 +1707,8 @@ impl App {
             );
 
             // #### egui ##########################################################################
-            self.egui_integration.begin_frame(&self.window);
+            self.egui_integration.begin_frame(&self.window)?;
+            self.egui_integration.set_frame_index(self.current_frame)?;
             egui::SidePanel::left("my_side_panel").show(&self.egui_integration.context(), |ui| {
                 ui.heading("User Texture Example");
                 ui.separator();
@@ -1769,20 +1773,20 @@ impl App {
                             );
                             ui.painter().add(Shape::Mesh(mesh));
 
-                            if response.dragged() {
-                                if ui.input(|input| {
+                            if response.dragged()
+                                && ui.input(|input| {
                                     input.pointer.button_down(PointerButton::Primary)
-                                }) {
-                                    if let Some(pointer_pos) =
-                                        ui.input(|input| input.pointer.interact_pos())
-                                    {
-                                        if let Some(prev_pointer_pos) = prev_pointer_pos {
-                                            let delta = pointer_pos - *prev_pointer_pos;
-                                            *rotation_x += delta.y * 2.0;
-                                            *rotation_y += -delta.x * 2.0;
-                                        }
-                                        *prev_pointer_pos = Some(pointer_pos);
+                                })
+                            {
+                                if let Some(pointer_pos) =
+                                    ui.input(|input| input.pointer.interact_pos())
+                                {
+                                    if let Some(prev_pointer_pos) = prev_pointer_pos {
+                                        let delta = pointer_pos - *prev_pointer_pos;
+                                        *rotation_x += delta.y * 2.0;
+                                        *rotation_y += -delta.x * 2.0;
                                     }
+                                    *prev_pointer_pos = Some(pointer_pos);
                                 }
                             }
                             if response.drag_stopped() {
@@ -1792,7 +1796,7 @@ impl App {
                         ui.label("You can drag the scene to rotate the model.");
                     });
             }
-            let output = self.egui_integration.end_frame(&mut self.window);
+            let output = self.egui_integration.end_frame(&self.window)?;
             let clipped_meshes = self
                 .egui_integration
                 .context()
@@ -1802,7 +1806,7 @@ impl App {
                 image_index,
                 clipped_meshes,
                 output.textures_delta,
-            );
+            )?;
             // #### egui ##########################################################################
 
             self.device.end_command_buffer(command_buffer)?;
@@ -1826,7 +1830,7 @@ impl App {
                     .image_indices(&[image_index as u32])
                     .wait_semaphores(&[self.render_finished_semaphores[self.current_frame]]),
             ) {
-                Ok(is_suboptimal) if is_suboptimal == true => return self.recreate_swapchain(),
+                Ok(is_suboptimal) if is_suboptimal => return self.recreate_swapchain(),
                 Ok(_is_suboptimal) => (),
                 Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return self.recreate_swapchain(),
                 Err(error) => panic!("Failed to present queue: {}", error),
@@ -1870,19 +1874,18 @@ impl App {
                     .get_physical_device_surface_present_modes(self.physical_device, self.surface)?
             };
 
-            self.format = formats
+            self.format = *formats
                 .iter()
                 .find(|f| {
                     f.format == vk::Format::B8G8R8A8_UNORM || f.format == vk::Format::R8G8B8A8_UNORM
                 })
-                .unwrap_or(&formats[0])
-                .clone();
+                .unwrap_or(&formats[0]);
             let present_mode = present_modes
                 .into_iter()
                 .find(|&p| p == vk::PresentModeKHR::MAILBOX)
                 .unwrap_or(vk::PresentModeKHR::FIFO);
             self.extent = {
-                if capabilities.current_extent.width != u32::max_value() {
+                if capabilities.current_extent.width != u32::MAX {
                     capabilities.current_extent
                 } else {
                     vk::Extent2D {
@@ -1950,9 +1953,9 @@ impl App {
         self.egui_integration.update_swapchain(
             self.width,
             self.height,
-            self.swapchain.clone(),
-            self.format.clone(),
-        );
+            self.swapchain,
+            self.format,
+        )?;
         // #### egui ##########################################################################
 
         Ok(())
@@ -1965,9 +1968,12 @@ impl Drop for App {
             self.device.queue_wait_idle(self.present_queue).unwrap();
 
             self.egui_integration
-                .unregister_user_texture(self.image_texture_id);
+                .unregister_user_texture(self.image_texture_id)
+                .unwrap();
             for &texture_id in self.scene_texture_ids.iter() {
-                self.egui_integration.unregister_user_texture(texture_id);
+                self.egui_integration
+                    .unregister_user_texture(texture_id)
+                    .unwrap();
             }
 
             self.device.destroy_image_view(self.image_view, None);
@@ -1977,7 +1983,6 @@ impl Drop for App {
             }
             self.device.destroy_sampler(self.sampler, None);
 
-            self.egui_integration.destroy();
             ManuallyDrop::drop(&mut self.egui_integration);
 
             for i in 0..MAX_FRAMES_IN_FLIGHT {
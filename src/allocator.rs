@@ -6,6 +6,9 @@ use ash::vk::*;
 
 pub trait AllocationTrait {
     /// Returns the vk::DeviceMemory object that is backing this allocation.
+    ///
+    /// # Safety
+    /// The memory is shared with other allocations, so it must not be freed and may only be used within this allocation.
     unsafe fn memory(&self) -> DeviceMemory;
 
     /// Returns the offset of the allocation on the vk::DeviceMemory. When binding the memory to a buffer or image, this offset needs to be supplied as well.
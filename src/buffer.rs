@@ -0,0 +1,162 @@
+use ash::{vk, Device};
+
+use crate::*;
+
+// Number of consecutive low-use frames before a buffer is shrunk.
+const SHRINK_AFTER_FRAMES: u32 = 300;
+
+// A host-visible buffer that is reallocated with geometric growth when a frame needs more space.
+pub(crate) struct GrowableBuffer<A: AllocatorTrait> {
+    buffer: vk::Buffer,
+    allocation: A::Allocation,
+    size: u64,
+    initial_size: u64,
+    usage: vk::BufferUsageFlags,
+    // queue families sharing the buffer concurrently, empty if it is used by a single family
+    queue_family_indices: Vec<u32>,
+    low_use_frames: u32,
+}
+impl<A: AllocatorTrait> GrowableBuffer<A> {
+    pub(crate) fn new(
+        device: &Device,
+        allocator: &A,
+        usage: vk::BufferUsageFlags,
+        size: u64,
+        queue_family_indices: &[u32],
+    ) -> Result<Self, IntegrationError> {
+        let (buffer, allocation) =
+            create_buffer(device, allocator, usage, size, queue_family_indices)?;
+        Ok(Self {
+            buffer,
+            allocation,
+            size,
+            initial_size: size,
+            usage,
+            queue_family_indices: queue_family_indices.to_vec(),
+            low_use_frames: 0,
+        })
+    }
+
+    pub(crate) fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    pub(crate) fn mapped_ptr(&self) -> Result<*mut u8, IntegrationError> {
+        let ptr = self.allocation.mapped_ptr().ok_or_else(|| {
+            IntegrationError::Allocator(anyhow::anyhow!("Buffer memory is not host visible"))
+        })?;
+        Ok(ptr.as_ptr() as *mut u8)
+    }
+
+    // Make room for `required` bytes, returning the replaced buffer.
+    //
+    // `shrink` should be set once per frame; the buffer is halved after a sustained period of frames
+    // using less than a quarter of it.
+    // The returned buffer may still be in use by the GPU and has to be destroyed later.
+    pub(crate) fn reserve(
+        &mut self,
+        device: &Device,
+        allocator: &A,
+        required: u64,
+        shrink: bool,
+    ) -> Result<Option<(vk::Buffer, A::Allocation)>, IntegrationError> {
+        let mut new_size = self.size;
+        if required > self.size {
+            while new_size < required {
+                new_size *= 2;
+            }
+            self.low_use_frames = 0;
+        } else if shrink {
+            if self.size > self.initial_size && required <= self.size / 4 {
+                self.low_use_frames += 1;
+                if self.low_use_frames >= SHRINK_AFTER_FRAMES {
+                    new_size = (self.size / 2).max(self.initial_size);
+                    self.low_use_frames = 0;
+                }
+            } else {
+                self.low_use_frames = 0;
+            }
+        }
+        if new_size == self.size {
+            return Ok(None);
+        }
+
+        let (buffer, allocation) = create_buffer(
+            device,
+            allocator,
+            self.usage,
+            new_size,
+            &self.queue_family_indices,
+        )?;
+        self.size = new_size;
+        Ok(Some((
+            std::mem::replace(&mut self.buffer, buffer),
+            std::mem::replace(&mut self.allocation, allocation),
+        )))
+    }
+
+    // Replace the buffer with one shared by `queue_family_indices`, returning the replaced buffer.
+    //
+    // The returned buffer may still be in use by the GPU and has to be destroyed later.
+    pub(crate) fn share(
+        &mut self,
+        device: &Device,
+        allocator: &A,
+        queue_family_indices: &[u32],
+    ) -> Result<(vk::Buffer, A::Allocation), IntegrationError> {
+        let (buffer, allocation) = create_buffer(
+            device,
+            allocator,
+            self.usage,
+            self.size,
+            queue_family_indices,
+        )?;
+        self.queue_family_indices = queue_family_indices.to_vec();
+        Ok((
+            std::mem::replace(&mut self.buffer, buffer),
+            std::mem::replace(&mut self.allocation, allocation),
+        ))
+    }
+
+    pub(crate) unsafe fn destroy(
+        self,
+        device: &Device,
+        allocator: &A,
+    ) -> Result<(), IntegrationError> {
+        device.destroy_buffer(self.buffer, None);
+        allocator.free(self.allocation)?;
+        Ok(())
+    }
+}
+
+fn create_buffer<A: AllocatorTrait>(
+    device: &Device,
+    allocator: &A,
+    usage: vk::BufferUsageFlags,
+    size: u64,
+    queue_family_indices: &[u32],
+) -> Result<(vk::Buffer, A::Allocation), IntegrationError> {
+    let sharing_mode = if queue_family_indices.is_empty() {
+        vk::SharingMode::EXCLUSIVE
+    } else {
+        vk::SharingMode::CONCURRENT
+    };
+    let buffer = unsafe {
+        device.create_buffer(
+            &vk::BufferCreateInfo::default()
+                .usage(usage)
+                .sharing_mode(sharing_mode)
+                .queue_family_indices(queue_family_indices)
+                .size(size),
+            None,
+        )?
+    };
+    let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+    let allocation = allocator.allocate(A::AllocationCreateInfo::new(
+        requirements,
+        MemoryLocation::CpuToGpu,
+        true,
+    ))?;
+    unsafe { device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset())? }
+    Ok((buffer, allocation))
+}
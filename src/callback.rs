@@ -0,0 +1,134 @@
+#![warn(missing_docs)]
+
+use std::any::{Any, TypeId};
+
+use ash::{vk, Device};
+use egui::{epaint::ahash::AHashMap, PaintCallback, PaintCallbackInfo, Rect};
+
+/// Vulkan state passed to [`CallbackTrait::paint`].
+pub struct CallbackContext<'a> {
+    /// The device the integration was created with.
+    pub device: &'a Device,
+    /// The command buffer the integration is recording into.
+    pub command_buffer: vk::CommandBuffer,
+    /// The viewport of [`PaintCallback::rect`] in physical pixels. It is already set on the command buffer.
+    pub viewport: vk::Viewport,
+    /// The clip rect in physical pixels. It is already set on the command buffer.
+    pub scissor: vk::Rect2D,
+    /// The render pass the integration is currently inside of.
+    ///
+    /// This is null in [`crate::RenderingMode::DynamicRendering`]; pipelines then need to be
+    /// created for dynamic rendering with [`Self::format`].
+    pub render_pass: vk::RenderPass,
+    /// The format of the color attachment painted to.
+    pub format: vk::Format,
+    /// The sample count of the color attachment, set with [`crate::Integration::set_sample_count`].
+    pub sample_count: vk::SampleCountFlags,
+    /// The resources registered with [`crate::Integration::callback_resources_mut`].
+    pub resources: &'a CallbackResources,
+}
+
+/// A custom draw that is recorded in the middle of the egui render pass.
+///
+/// Pipelines used here must be compatible with [`CallbackContext::render_pass`] subpass 0,
+/// or with [`CallbackContext::format`] in dynamic rendering mode.
+pub trait CallbackTrait: Send + Sync {
+    /// Record draw commands.
+    ///
+    /// The pipeline, buffers and push constants of the integration are bound again afterwards,
+    /// so any state may be changed here.
+    fn paint(&self, info: PaintCallbackInfo, context: &CallbackContext);
+}
+
+/// The callback type the integration expects in [`PaintCallback::callback`].
+pub struct Callback(Box<dyn CallbackTrait>);
+impl Callback {
+    /// Create a [`PaintCallback`] that paints `callback` in `rect`.
+    pub fn new_paint_callback(rect: Rect, callback: impl CallbackTrait + 'static) -> PaintCallback {
+        PaintCallback {
+            rect,
+            callback: std::sync::Arc::new(Self(Box::new(callback))),
+        }
+    }
+
+    pub(crate) fn paint(&self, info: PaintCallbackInfo, context: &CallbackContext) {
+        self.0.paint(info, context);
+    }
+}
+
+/// A resource stored in [`CallbackResources`], such as a pipeline, buffers or descriptor sets used by callbacks.
+pub trait CallbackResource: 'static {
+    /// Called every paint before the render pass begins, e.g. to upload data or insert barriers.
+    fn prepare(&mut self, _device: &Device, _command_buffer: vk::CommandBuffer) {}
+
+    /// Destroy the Vulkan objects owned by this resource.
+    ///
+    /// # Safety
+    /// Called from [`crate::Integration::destroy`]; the GPU must no longer use the resource.
+    unsafe fn destroy(&mut self, device: &Device);
+}
+
+struct ResourceEntry {
+    value: Box<dyn Any>,
+    prepare: fn(&mut dyn Any, &Device, vk::CommandBuffer),
+    destroy: unsafe fn(&mut dyn Any, &Device),
+}
+
+/// Type-keyed storage for resources shared between paint callbacks.
+#[derive(Default)]
+pub struct CallbackResources {
+    entries: AHashMap<TypeId, ResourceEntry>,
+}
+impl CallbackResources {
+    /// Insert a resource, returning the previous one of the same type.
+    ///
+    /// The returned resource is not destroyed.
+    pub fn insert<T: CallbackResource>(&mut self, resource: T) -> Option<T> {
+        let entry = ResourceEntry {
+            value: Box::new(resource),
+            prepare: |value, device, command_buffer| {
+                value
+                    .downcast_mut::<T>()
+                    .unwrap()
+                    .prepare(device, command_buffer)
+            },
+            destroy: |value, device| unsafe { value.downcast_mut::<T>().unwrap().destroy(device) },
+        };
+        self.entries
+            .insert(TypeId::of::<T>(), entry)
+            .map(|old| *old.value.downcast::<T>().unwrap())
+    }
+
+    /// Get a resource by type.
+    pub fn get<T: CallbackResource>(&self) -> Option<&T> {
+        self.entries
+            .get(&TypeId::of::<T>())
+            .and_then(|entry| entry.value.downcast_ref())
+    }
+
+    /// Get a resource by type mutably.
+    pub fn get_mut<T: CallbackResource>(&mut self) -> Option<&mut T> {
+        self.entries
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|entry| entry.value.downcast_mut())
+    }
+
+    /// Remove a resource without destroying it.
+    pub fn remove<T: CallbackResource>(&mut self) -> Option<T> {
+        self.entries
+            .remove(&TypeId::of::<T>())
+            .map(|entry| *entry.value.downcast::<T>().unwrap())
+    }
+
+    pub(crate) fn prepare(&mut self, device: &Device, command_buffer: vk::CommandBuffer) {
+        for entry in self.entries.values_mut() {
+            (entry.prepare)(entry.value.as_mut(), device, command_buffer);
+        }
+    }
+
+    pub(crate) unsafe fn destroy(&mut self, device: &Device) {
+        for (_, mut entry) in self.entries.drain() {
+            (entry.destroy)(entry.value.as_mut(), device);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+#![warn(missing_docs)]
+
+use ash::{khr::swapchain::Device as Swapchain, vk, Device};
+use raw_window_handle::HasDisplayHandle;
+
+use crate::*;
+
+/// Everything an [`Integration`] is created from, with defaults for the settings.
+///
+/// Start with [`Self::new`], choose a swapchain with [`Self::swapchain`] or an offscreen image with
+/// [`Self::headless`], override settings as needed and create the integration with [`Self::build`].
+pub struct IntegrationDescriptor<'a, A: AllocatorTrait> {
+    pub(crate) display_target: Option<&'a dyn HasDisplayHandle>,
+    pub(crate) swapchain: Option<(Swapchain, vk::SwapchainKHR)>,
+    pub(crate) format: Option<vk::Format>,
+    pub(crate) physical_width: u32,
+    pub(crate) physical_height: u32,
+    pub(crate) scale_factor: f64,
+    pub(crate) device: Device,
+    pub(crate) allocator: A,
+    pub(crate) qfi: u32,
+    pub(crate) queue: vk::Queue,
+    pub(crate) max_texture_side: usize,
+    pub(crate) font_definitions: egui::FontDefinitions,
+    pub(crate) style: egui::Style,
+    pub(crate) rendering_mode: RenderingMode,
+    pub(crate) target_desc: Option<TargetDesc>,
+    pub(crate) sample_count: vk::SampleCountFlags,
+    pub(crate) frames_in_flight: Option<usize>,
+    pub(crate) shrink_buffers: bool,
+    pub(crate) vertex_buffer_size: u64,
+    pub(crate) index_buffer_size: u64,
+    pub(crate) staging_buffer_size: u64,
+    pub(crate) descriptor_pool_size: u32,
+    pub(crate) destroy_on_drop: bool,
+}
+impl<'a, A: AllocatorTrait> IntegrationDescriptor<'a, A> {
+    /// Describe an integration of the given physical size and scale factor on `queue` of family `qfi`.
+    pub fn new(
+        physical_width: u32,
+        physical_height: u32,
+        scale_factor: f64,
+        device: Device,
+        allocator: A,
+        qfi: u32,
+        queue: vk::Queue,
+    ) -> Self {
+        Self {
+            display_target: None,
+            swapchain: None,
+            format: None,
+            physical_width,
+            physical_height,
+            scale_factor,
+            device,
+            allocator,
+            qfi,
+            queue,
+            max_texture_side: 4096,
+            font_definitions: egui::FontDefinitions::default(),
+            style: egui::Style::default(),
+            rendering_mode: RenderingMode::RenderPass,
+            target_desc: None,
+            sample_count: vk::SampleCountFlags::TYPE_1,
+            frames_in_flight: None,
+            shrink_buffers: false,
+            vertex_buffer_size: 1024 * 1024 * 4,
+            index_buffer_size: 1024 * 1024 * 2,
+            staging_buffer_size: 1024 * 1024 * 4,
+            descriptor_pool_size: 1024,
+            destroy_on_drop: true,
+        }
+    }
+
+    /// Paint into the images of `swapchain`, with input from the window of `display_target`.
+    pub fn swapchain<H: HasDisplayHandle>(
+        mut self,
+        display_target: &'a H,
+        swapchain_loader: Swapchain,
+        swapchain: vk::SwapchainKHR,
+        surface_format: vk::SurfaceFormatKHR,
+    ) -> Self {
+        self.display_target = Some(display_target);
+        self.swapchain = Some((swapchain_loader, swapchain));
+        self.format = Some(surface_format.format);
+        self
+    }
+
+    /// Paint into an offscreen image of `format` owned by the integration, without a window.
+    ///
+    /// See [`Integration::new_headless`].
+    pub fn headless(mut self, format: vk::Format) -> Self {
+        self.display_target = None;
+        self.swapchain = None;
+        self.format = Some(format);
+        self
+    }
+
+    /// Set the maximum texture size, usually `maxImageDimension2D` of the device. Defaults to 4096,
+    /// which every Vulkan device supports.
+    pub fn max_texture_side(mut self, max_texture_side: usize) -> Self {
+        self.max_texture_side = max_texture_side;
+        self
+    }
+
+    /// Set the fonts of the egui context.
+    pub fn font_definitions(mut self, font_definitions: egui::FontDefinitions) -> Self {
+        self.font_definitions = font_definitions;
+        self
+    }
+
+    /// Set the style of the egui context.
+    pub fn style(mut self, style: egui::Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Set how rendering is begun and ended. Defaults to [`RenderingMode::RenderPass`].
+    pub fn rendering_mode(mut self, rendering_mode: RenderingMode) -> Self {
+        self.rendering_mode = rendering_mode;
+        self
+    }
+
+    /// Set how the swapchain or offscreen image is loaded and transitioned, see [`Integration::set_target_desc`].
+    pub fn target_desc(mut self, target_desc: TargetDesc) -> Self {
+        self.target_desc = Some(target_desc);
+        self
+    }
+
+    /// Set the number of samples egui is rendered with, see [`Integration::set_sample_count`].
+    pub fn sample_count(mut self, sample_count: vk::SampleCountFlags) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Set the number of frames in flight, see [`Integration::set_frames_in_flight`].
+    /// Defaults to the number of swapchain images.
+    pub fn frames_in_flight(mut self, frames_in_flight: usize) -> Self {
+        self.frames_in_flight = Some(frames_in_flight);
+        self
+    }
+
+    /// Set whether buffers are shrunk after a sustained period of low use, see [`Integration::set_shrink_buffers`].
+    pub fn shrink_buffers(mut self, shrink_buffers: bool) -> Self {
+        self.shrink_buffers = shrink_buffers;
+        self
+    }
+
+    /// Set the initial sizes in bytes of the vertex, index and texture staging buffers of each frame in flight.
+    ///
+    /// Defaults to 4 MiB, 2 MiB and 4 MiB. The buffers grow as needed.
+    pub fn buffer_sizes(mut self, vertex: u64, index: u64, staging: u64) -> Self {
+        self.vertex_buffer_size = vertex;
+        self.index_buffer_size = index;
+        self.staging_buffer_size = staging;
+        self
+    }
+
+    /// Set the number of texture descriptor sets each descriptor pool holds. Defaults to 1024.
+    ///
+    /// Another pool is created whenever the existing ones are exhausted.
+    pub fn descriptor_pool_size(mut self, descriptor_pool_size: u32) -> Self {
+        self.descriptor_pool_size = descriptor_pool_size;
+        self
+    }
+
+    /// Set whether dropping the integration destroys its vk objects, see [`Integration::set_destroy_on_drop`].
+    pub fn destroy_on_drop(mut self, destroy_on_drop: bool) -> Self {
+        self.destroy_on_drop = destroy_on_drop;
+        self
+    }
+
+    /// Create the integration, returning [`IntegrationError::Misuse`] for invalid combinations of settings.
+    pub fn build(self) -> Result<Integration<A>, IntegrationError> {
+        self.validate()?;
+        Integration::from_descriptor(self)
+    }
+
+    // The target description set, or the default for the swapchain or offscreen image.
+    pub(crate) fn resolved_target_desc(&self) -> TargetDesc {
+        match (self.target_desc, &self.swapchain) {
+            (Some(target_desc), _) => target_desc,
+            (None, Some(_)) => TargetDesc::default(),
+            (None, None) => TargetDesc {
+                load: AttachmentLoad::Clear([0.0; 4]),
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            },
+        }
+    }
+
+    fn validate(&self) -> Result<(), IntegrationError> {
+        if self.format.is_none() {
+            return Err(IntegrationError::Misuse(
+                "Either a swapchain or a headless format is required",
+            ));
+        }
+        if self.physical_width == 0 || self.physical_height == 0 {
+            return Err(IntegrationError::Misuse(
+                "The physical size must not be zero",
+            ));
+        }
+        if self.frames_in_flight == Some(0) {
+            return Err(IntegrationError::Misuse(
+                "frames_in_flight must be at least 1",
+            ));
+        }
+        if self.sample_count.as_raw().count_ones() != 1 {
+            return Err(IntegrationError::Misuse(
+                "sample_count must be a single sample count",
+            ));
+        }
+        if self.sample_count != vk::SampleCountFlags::TYPE_1
+            && self.resolved_target_desc().load == AttachmentLoad::Load
+        {
+            return Err(IntegrationError::Misuse(
+                "AttachmentLoad::Load is not supported with multisampling",
+            ));
+        }
+        if self.vertex_buffer_size == 0
+            || self.index_buffer_size == 0
+            || self.staging_buffer_size == 0
+        {
+            return Err(IntegrationError::Misuse("Buffer sizes must not be zero"));
+        }
+        if self.descriptor_pool_size == 0 {
+            return Err(IntegrationError::Misuse(
+                "The descriptor pool size must not be zero",
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use ash::{vk, Device};
+
+use crate::*;
+
+// A descriptor set together with the index of the pool it was allocated from, needed to free it.
+#[derive(Clone, Copy)]
+pub(crate) struct PooledDescriptorSet {
+    pub(crate) set: vk::DescriptorSet,
+    pub(crate) pool: usize,
+}
+
+// Descriptor pools of combined image samplers, chained with a new pool whenever the existing ones are exhausted
+// or on the first allocation.
+pub(crate) struct DescriptorPools {
+    pools: Vec<vk::DescriptorPool>,
+    // number of sets allocated from each pool
+    allocated: Vec<u32>,
+    pool_size: u32,
+}
+impl DescriptorPools {
+    pub(crate) fn new(pool_size: u32) -> Self {
+        Self {
+            pools: vec![],
+            allocated: vec![],
+            pool_size,
+        }
+    }
+
+    // Allocate a set of `layout`, which must hold a single combined image sampler.
+    pub(crate) fn allocate(
+        &mut self,
+        device: &Device,
+        layout: vk::DescriptorSetLayout,
+    ) -> Result<PooledDescriptorSet, IntegrationError> {
+        for pool in 0..self.pools.len() {
+            if self.allocated[pool] >= self.pool_size {
+                continue;
+            }
+            match self.allocate_from(device, pool, layout) {
+                // A pool with free sets can still be too fragmented to allocate from.
+                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL) => {}
+                result => return Ok(result?),
+            }
+        }
+        self.push_pool(device)?;
+        Ok(self.allocate_from(device, self.pools.len() - 1, layout)?)
+    }
+
+    pub(crate) fn free(
+        &mut self,
+        device: &Device,
+        sets: &[PooledDescriptorSet],
+    ) -> Result<(), IntegrationError> {
+        for set in sets {
+            unsafe {
+                device.free_descriptor_sets(self.pools[set.pool], &[set.set])?;
+            }
+            self.allocated[set.pool] -= 1;
+        }
+        Ok(())
+    }
+
+    // Destroy the pools, which frees every set allocated from them.
+    pub(crate) unsafe fn destroy(&mut self, device: &Device) {
+        for pool in self.pools.drain(..) {
+            device.destroy_descriptor_pool(pool, None);
+        }
+        self.allocated.clear();
+    }
+
+    fn allocate_from(
+        &mut self,
+        device: &Device,
+        pool: usize,
+        layout: vk::DescriptorSetLayout,
+    ) -> Result<PooledDescriptorSet, vk::Result> {
+        let set = unsafe {
+            device.allocate_descriptor_sets(
+                &vk::DescriptorSetAllocateInfo::default()
+                    .descriptor_pool(self.pools[pool])
+                    .set_layouts(std::slice::from_ref(&layout)),
+            )
+        }?[0];
+        self.allocated[pool] += 1;
+        Ok(PooledDescriptorSet { set, pool })
+    }
+
+    fn push_pool(&mut self, device: &Device) -> Result<(), IntegrationError> {
+        let pool = unsafe {
+            device.create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::default()
+                    .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+                    .max_sets(self.pool_size)
+                    .pool_sizes(&[vk::DescriptorPoolSize::default()
+                        .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .descriptor_count(self.pool_size)]),
+                None,
+            )
+        }?;
+        self.pools.push(pool);
+        self.allocated.push(0);
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+
+use ash::vk;
+
+/// An error returned by [`crate::Integration`].
+#[derive(Debug)]
+pub enum IntegrationError {
+    /// A Vulkan call failed, e.g. with `ERROR_OUT_OF_DEVICE_MEMORY` or `ERROR_DEVICE_LOST`.
+    Vulkan(vk::Result),
+    /// The allocator failed to allocate or free memory.
+    Allocator(anyhow::Error),
+    /// The swapchain no longer matches the surface and has to be recreated.
+    SwapchainOutOfDate,
+    /// The integration was used in a way it does not support.
+    Misuse(&'static str),
+}
+
+impl fmt::Display for IntegrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrationError::Vulkan(result) => write!(f, "Vulkan error: {}", result),
+            IntegrationError::Allocator(error) => write!(f, "Allocator error: {}", error),
+            IntegrationError::SwapchainOutOfDate => write!(f, "The swapchain is out of date"),
+            IntegrationError::Misuse(message) => write!(f, "Misuse: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for IntegrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IntegrationError::Allocator(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<vk::Result> for IntegrationError {
+    fn from(result: vk::Result) -> Self {
+        match result {
+            vk::Result::ERROR_OUT_OF_DATE_KHR => IntegrationError::SwapchainOutOfDate,
+            result => IntegrationError::Vulkan(result),
+        }
+    }
+}
+
+impl From<anyhow::Error> for IntegrationError {
+    fn from(error: anyhow::Error) -> Self {
+        IntegrationError::Allocator(error)
+    }
+}
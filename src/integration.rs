@@ -4,13 +4,448 @@ use ash::{khr::swapchain::Device as Swapchain, vk, Device};
 use bytemuck::bytes_of;
 use egui::{
     epaint::{ahash::AHashMap, ImageDelta},
-    Context, TextureId, TexturesDelta,
+    Context, DeferredViewportUiCallback, TextureId, TexturesDelta, ViewportBuilder, ViewportId,
+    ViewportIdMap, ViewportInfo, ViewportOutput,
+};
+use egui_winit::{
+    winit::window::{Window, WindowId},
+    EventResponse,
 };
-use egui_winit::{winit::window::Window, EventResponse};
 use raw_window_handle::HasDisplayHandle;
-use std::ffi::CString;
+use std::{ffi::CString, sync::Arc};
+
+use crate::{
+    buffer::GrowableBuffer,
+    descriptor_pool::{DescriptorPools, PooledDescriptorSet},
+    utils::insert_image_memory_barrier,
+    *,
+};
+
+/// How the integration begins and ends rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingMode {
+    /// Draw in a `vk::RenderPass` owned by the integration, with a framebuffer per swapchain image.
+    RenderPass,
+    /// Draw with `cmd_begin_rendering`/`cmd_end_rendering` (Vulkan 1.3 or `VK_KHR_dynamic_rendering`).
+    ///
+    /// The `dynamicRendering` feature must be enabled on the device.
+    DynamicRendering,
+}
+
+/// What happens to the contents of the target image when egui starts drawing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttachmentLoad {
+    /// Keep the contents, drawing egui on top of them.
+    Load,
+    /// Clear the image to an RGBA color first.
+    Clear([f32; 4]),
+    /// The contents are not needed, e.g. when egui covers the whole image.
+    DontCare,
+}
+impl AttachmentLoad {
+    fn load_op(&self) -> vk::AttachmentLoadOp {
+        match self {
+            AttachmentLoad::Load => vk::AttachmentLoadOp::LOAD,
+            AttachmentLoad::Clear(_) => vk::AttachmentLoadOp::CLEAR,
+            AttachmentLoad::DontCare => vk::AttachmentLoadOp::DONT_CARE,
+        }
+    }
+
+    fn clear_value(&self) -> vk::ClearValue {
+        let float32 = match *self {
+            AttachmentLoad::Clear(color) => color,
+            _ => [0.0; 4],
+        };
+        vk::ClearValue {
+            color: vk::ClearColorValue { float32 },
+        }
+    }
+}
+
+/// How [`Integration::paint`] uses the swapchain images.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetDesc {
+    /// What happens to the contents of the image when egui starts drawing.
+    pub load: AttachmentLoad,
+    /// The layout of the image when the paint commands start executing.
+    ///
+    /// Use `UNDEFINED` when egui is the first pass on the image and `load` is not [`AttachmentLoad::Load`].
+    pub initial_layout: vk::ImageLayout,
+    /// The layout the image is left in, e.g. `SHADER_READ_ONLY_OPTIMAL` for a later post-process pass.
+    pub final_layout: vk::ImageLayout,
+}
+impl Default for TargetDesc {
+    fn default() -> Self {
+        Self {
+            load: AttachmentLoad::Load,
+            initial_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        }
+    }
+}
+
+/// An image to paint egui into with [`Integration::paint_to_target`].
+#[derive(Debug, Clone, Copy)]
+pub struct PaintTarget {
+    /// The image to paint into.
+    pub image: vk::Image,
+    /// A 2D color view of `image` with a single mip level and layer.
+    pub image_view: vk::ImageView,
+    /// The size of `image`.
+    pub extent: vk::Extent2D,
+    /// The format of `image_view`.
+    pub format: vk::Format,
+    /// What happens to the contents of the image when egui starts drawing.
+    pub load: AttachmentLoad,
+    /// The layout of the image when the paint commands start executing.
+    pub initial_layout: vk::ImageLayout,
+    /// The layout the image is left in.
+    pub final_layout: vk::ImageLayout,
+}
+
+/// A timeline semaphore value the graphics submit of a frame has to signal.
+///
+/// Returned by the paint calls once [`Integration::set_timeline_semaphore`] has been called.
+/// A returned value must be signalled, e.g. by an empty submit if the frame is abandoned, since the
+/// integration waits for it before reusing the frame's buffers. Paint calls that fail don't hand out a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimelineSignal {
+    /// The timeline semaphore passed to [`Integration::set_timeline_semaphore`].
+    pub semaphore: vk::Semaphore,
+    /// The value to signal once the GPU has finished the paint commands of the frame.
+    pub value: u64,
+}
+
+/// The window and swapchain of a viewport other than the root viewport, created by the application.
+pub struct ViewportWindow {
+    /// The native window of the viewport.
+    pub window: Window,
+    /// The surface of `window`.
+    pub surface: vk::SurfaceKHR,
+    /// The swapchain of `surface`, created with `COLOR_ATTACHMENT` usage and the inner size of `window`.
+    pub swapchain: vk::SwapchainKHR,
+    /// The format of `swapchain`.
+    pub surface_format: vk::SurfaceFormatKHR,
+}
+
+// The state of a viewport other than the root viewport.
+struct ViewportState {
+    window: ViewportWindow,
+    egui_winit: egui_winit::State,
+    info: ViewportInfo,
+    builder: ViewportBuilder,
+    viewport_ui_cb: Option<Arc<DeferredViewportUiCallback>>,
+    scale_factor: f64,
+    physical_width: u32,
+    physical_height: u32,
+    swapchain_images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
+    framebuffers: Vec<vk::Framebuffer>,
+}
+impl ViewportState {
+    fn extent(&self) -> vk::Extent2D {
+        vk::Extent2D::default()
+            .width(self.physical_width)
+            .height(self.physical_height)
+    }
+}
+
+// Where a paint call draws to.
+struct RenderTarget {
+    extent: vk::Extent2D,
+    format: vk::Format,
+    pixels_per_point: f32,
+    pipeline: vk::Pipeline,
+    load: AttachmentLoad,
+    attachment: TargetAttachment,
+}
+
+enum TargetAttachment {
+    Framebuffer {
+        render_pass: vk::RenderPass,
+        framebuffer: vk::Framebuffer,
+    },
+    ImageView {
+        image_view: vk::ImageView,
+        // the multisampled image rendered to before resolving into `image_view`
+        msaa: Option<(vk::Image, vk::ImageView)>,
+        // the image and the layouts to transition from and to around rendering
+        transition: Option<(vk::Image, vk::ImageLayout, vk::ImageLayout)>,
+    },
+}
+impl TargetAttachment {
+    fn render_pass(&self) -> vk::RenderPass {
+        match *self {
+            TargetAttachment::Framebuffer { render_pass, .. } => render_pass,
+            TargetAttachment::ImageView { .. } => vk::RenderPass::null(),
+        }
+    }
+}
+
+// Initial sizes of the buffers of each frame in flight.
+#[derive(Clone, Copy)]
+struct BufferSizes {
+    vertex: u64,
+    index: u64,
+    staging: u64,
+}
+
+// Buffers used by the paint calls of one frame in flight, and objects waiting for the frame to finish.
+struct FrameBuffers<A: AllocatorTrait> {
+    vertex_buffer: GrowableBuffer<A>,
+    index_buffer: GrowableBuffer<A>,
+    staging_buffer: GrowableBuffer<A>,
+    // bytes already used by earlier paint calls of the current frame
+    vertex_offset: u64,
+    index_offset: u64,
+    staging_offset: u64,
+    // value of the frame counter when this slot was last reset
+    frame: u64,
+    // timeline value signaled when the GPU has finished the frame using this slot, 0 if none was handed out
+    timeline_value: u64,
+    // buffers replaced while growing, destroyed the next time this slot is reset
+    retired_buffers: Vec<(vk::Buffer, A::Allocation)>,
+    // textures replaced by a full update or freed by egui, destroyed the next time this slot is reset
+    retired_textures: Vec<RetiredTexture<A>>,
+    // descriptor sets of unregistered user textures, freed the next time this slot is reset
+    retired_descriptor_sets: Vec<PooledDescriptorSet>,
+}
+impl<A: AllocatorTrait> FrameBuffers<A> {
+    fn new(
+        device: &Device,
+        allocator: &A,
+        sizes: BufferSizes,
+        staging_queue_family_indices: &[u32],
+    ) -> Result<Self, IntegrationError> {
+        Ok(Self {
+            vertex_buffer: GrowableBuffer::new(
+                device,
+                allocator,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                sizes.vertex,
+                &[],
+            )?,
+            index_buffer: GrowableBuffer::new(
+                device,
+                allocator,
+                vk::BufferUsageFlags::INDEX_BUFFER,
+                sizes.index,
+                &[],
+            )?,
+            staging_buffer: GrowableBuffer::new(
+                device,
+                allocator,
+                vk::BufferUsageFlags::TRANSFER_SRC,
+                sizes.staging,
+                staging_queue_family_indices,
+            )?,
+            vertex_offset: 0,
+            index_offset: 0,
+            staging_offset: 0,
+            frame: 0,
+            timeline_value: 0,
+            retired_buffers: vec![],
+            retired_textures: vec![],
+            retired_descriptor_sets: vec![],
+        })
+    }
+
+    // Destroy the objects retired during the last use of this slot.
+    fn destroy_retired(
+        &mut self,
+        device: &Device,
+        allocator: &A,
+        descriptor_pools: &mut DescriptorPools,
+    ) -> Result<(), IntegrationError> {
+        for (buffer, allocation) in self.retired_buffers.drain(..) {
+            unsafe {
+                device.destroy_buffer(buffer, None);
+            }
+            allocator.free(allocation)?;
+        }
+        for texture in self.retired_textures.drain(..) {
+            unsafe {
+                device.destroy_image_view(texture.image_view, None);
+                device.destroy_image(texture.image, None);
+            }
+            allocator.free(texture.allocation)?;
+            descriptor_pools.free(device, &[texture.descriptor_set])?;
+        }
+        descriptor_pools.free(device, &self.retired_descriptor_sets)?;
+        self.retired_descriptor_sets.clear();
+        Ok(())
+    }
+
+    unsafe fn destroy(
+        mut self,
+        device: &Device,
+        allocator: &A,
+        descriptor_pools: &mut DescriptorPools,
+    ) -> Result<(), IntegrationError> {
+        self.destroy_retired(device, allocator, descriptor_pools)?;
+        self.vertex_buffer.destroy(device, allocator)?;
+        self.index_buffer.destroy(device, allocator)?;
+        self.staging_buffer.destroy(device, allocator)
+    }
+}
+
+// A queue of a separate family that new textures are uploaded on.
+struct TransferQueue {
+    queue_family_index: u32,
+    queue: vk::Queue,
+    frames: Vec<TransferFrame>,
+}
+impl TransferQueue {
+    // Get the upload objects of a frame in flight, creating them on first use.
+    fn frame(
+        &mut self,
+        device: &Device,
+        frame_index: usize,
+    ) -> Result<&mut TransferFrame, IntegrationError> {
+        while self.frames.len() <= frame_index {
+            let command_pool = unsafe {
+                device.create_command_pool(
+                    &vk::CommandPoolCreateInfo::default()
+                        .flags(
+                            vk::CommandPoolCreateFlags::TRANSIENT
+                                | vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+                        )
+                        .queue_family_index(self.queue_family_index),
+                    None,
+                )
+            }?;
+            self.frames.push(TransferFrame {
+                command_pool,
+                command_buffers: vec![],
+                semaphores: vec![],
+                submitted: 0,
+            });
+        }
+        Ok(&mut self.frames[frame_index])
+    }
+
+    // Make the upload objects of a frame in flight reusable once the GPU has finished the frame.
+    fn reset(&mut self, device: &Device, frame_index: usize) -> Result<(), IntegrationError> {
+        let frame = self.frame(device, frame_index)?;
+        unsafe {
+            device.reset_command_pool(frame.command_pool, vk::CommandPoolResetFlags::empty())?;
+        }
+        frame.submitted = 0;
+        Ok(())
+    }
+
+    // Begin the next upload command buffer of a frame in flight.
+    fn begin(
+        &mut self,
+        device: &Device,
+        frame_index: usize,
+    ) -> Result<vk::CommandBuffer, IntegrationError> {
+        let frame = self.frame(device, frame_index)?;
+        if frame.submitted == frame.command_buffers.len() {
+            let command_buffer = unsafe {
+                device.allocate_command_buffers(
+                    &vk::CommandBufferAllocateInfo::default()
+                        .command_pool(frame.command_pool)
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(1),
+                )
+            }?[0];
+            let semaphore =
+                unsafe { device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None) }?;
+            frame.command_buffers.push(command_buffer);
+            frame.semaphores.push(semaphore);
+        }
+        let command_buffer = frame.command_buffers[frame.submitted];
+        unsafe {
+            device.begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )?;
+        }
+        Ok(command_buffer)
+    }
+
+    // Submit the upload command buffer begun last, signaling its semaphore.
+    fn submit(&mut self, device: &Device, frame_index: usize) -> Result<(), IntegrationError> {
+        let queue = self.queue;
+        let frame = self.frame(device, frame_index)?;
+        let command_buffer = frame.command_buffers[frame.submitted];
+        let semaphore = frame.semaphores[frame.submitted];
+        unsafe {
+            device.end_command_buffer(command_buffer)?;
+            device.queue_submit(
+                queue,
+                &[vk::SubmitInfo::default()
+                    .command_buffers(&[command_buffer])
+                    .signal_semaphores(&[semaphore])],
+                vk::Fence::null(),
+            )?;
+        }
+        frame.submitted += 1;
+        Ok(())
+    }
+
+    // Reset the upload command buffer begun last when recording or submitting it failed, so it can be begun again.
+    fn cancel(&mut self, device: &Device, frame_index: usize) -> Result<(), IntegrationError> {
+        let frame = self.frame(device, frame_index)?;
+        unsafe {
+            device.reset_command_buffer(
+                frame.command_buffers[frame.submitted],
+                vk::CommandBufferResetFlags::empty(),
+            )?;
+        }
+        Ok(())
+    }
+
+    unsafe fn destroy(self, device: &Device) {
+        for frame in self.frames {
+            for semaphore in frame.semaphores {
+                device.destroy_semaphore(semaphore, None);
+            }
+            device.destroy_command_pool(frame.command_pool, None);
+        }
+    }
+}
+
+// Upload command buffers of one frame in flight and the semaphores they signal.
+struct TransferFrame {
+    command_pool: vk::CommandPool,
+    command_buffers: Vec<vk::CommandBuffer>,
+    semaphores: Vec<vk::Semaphore>,
+    // number of uploads submitted in the current frame
+    submitted: usize,
+}
+
+// A managed texture that was replaced while the GPU may still be using it.
+struct RetiredTexture<A: AllocatorTrait> {
+    image: vk::Image,
+    image_view: vk::ImageView,
+    allocation: A::Allocation,
+    descriptor_set: PooledDescriptorSet,
+}
 
-use crate::{utils::insert_image_memory_barrier, *};
+// A color attachment image owned by the integration, e.g. a multisampled image resolved into the target.
+struct AttachmentImage<A: AllocatorTrait> {
+    image: vk::Image,
+    image_view: vk::ImageView,
+    allocation: A::Allocation,
+}
+
+// A copy of an image into a host-visible buffer.
+struct Readback<A: AllocatorTrait> {
+    buffer: vk::Buffer,
+    allocation: A::Allocation,
+    format: vk::Format,
+    extent: vk::Extent2D,
+}
+
+// A framebuffer created for a [`PaintTarget`].
+struct TargetFramebuffer {
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+    format: vk::Format,
+}
 
 /// egui integration with winit and ash.
 pub struct Integration<A: AllocatorTrait> {
@@ -18,36 +453,75 @@ pub struct Integration<A: AllocatorTrait> {
     physical_height: u32,
     scale_factor: f64,
     context: Context,
-    egui_winit: egui_winit::State,
+    egui_winit: Option<egui_winit::State>,
+    max_texture_side: usize,
+    viewports: AHashMap<ViewportId, ViewportState>,
 
     device: Device,
     allocator: A,
     qfi: u32,
     queue: vk::Queue,
-    swapchain_loader: Swapchain,
-    descriptor_pool: vk::DescriptorPool,
+    swapchain_loader: Option<Swapchain>,
+    // the image painted by headless integrations in place of swapchain images
+    offscreen_image: Option<AttachmentImage<A>>,
+    descriptor_pools: DescriptorPools,
     descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
     pipeline_layout: vk::PipelineLayout,
     pipeline: vk::Pipeline,
-    sampler: vk::Sampler,
+    samplers: AHashMap<egui::TextureOptions, vk::Sampler>,
+    rendering_mode: RenderingMode,
+    target_desc: TargetDesc,
     render_pass: vk::RenderPass,
+    swapchain_format: vk::Format,
+    swapchain_images: Vec<vk::Image>,
     framebuffer_color_image_views: Vec<vk::ImageView>,
     framebuffers: Vec<vk::Framebuffer>,
-    vertex_buffers: Vec<vk::Buffer>,
-    vertex_buffer_allocations: Vec<A::Allocation>,
-    index_buffers: Vec<vk::Buffer>,
-    index_buffer_allocations: Vec<A::Allocation>,
-    texture_desc_sets: AHashMap<TextureId, vk::DescriptorSet>,
+    // render passes for the swapchain and paint targets by format, sample count, load op and layouts
+    render_passes: AHashMap<
+        (
+            vk::Format,
+            vk::SampleCountFlags,
+            vk::AttachmentLoadOp,
+            vk::ImageLayout,
+            vk::ImageLayout,
+        ),
+        vk::RenderPass,
+    >,
+    target_pipelines: AHashMap<vk::Format, vk::Pipeline>,
+    target_framebuffers: AHashMap<vk::ImageView, TargetFramebuffer>,
+    sample_count: vk::SampleCountFlags,
+    msaa_images: AHashMap<(vk::Format, vk::Extent2D), AttachmentImage<A>>,
+    buffer_sizes: BufferSizes,
+    frame_buffers: Vec<FrameBuffers<A>>,
+    transfer_queue: Option<TransferQueue>,
+    // the timeline semaphore and the value of the latest frame
+    timeline: Option<(vk::Semaphore, u64)>,
+    // a screenshot requested by egui, copied by the next paint call that knows the image it draws to and
+    // delivered at the start of the frame after
+    screenshot_requested: bool,
+    screenshot: Option<Readback<A>>,
+    frame_index: usize,
+    frame_counter: u64,
+    manual_frame_index: bool,
+    shrink_buffers: bool,
+    // whether dropping the integration destroys its vk objects, and whether they have been destroyed
+    destroy_on_drop: bool,
+    destroyed: bool,
+    texture_desc_sets: AHashMap<TextureId, PooledDescriptorSet>,
     texture_images: AHashMap<TextureId, vk::Image>,
-    texture_image_infos: AHashMap<TextureId, vk::ImageCreateInfo<'static>>,
     texture_allocations: AHashMap<TextureId, A::Allocation>,
     texture_image_views: AHashMap<TextureId, vk::ImageView>,
 
     user_texture_layout: vk::DescriptorSetLayout,
-    user_textures: Vec<Option<vk::DescriptorSet>>,
+    user_textures: UserTextures,
+
+    callback_resources: CallbackResources,
 }
 impl<A: AllocatorTrait> Integration<A> {
     /// Create an instance of the integration.
+    ///
+    /// See [`IntegrationDescriptor`] for more settings.
+    #[allow(clippy::too_many_arguments)]
     pub fn new<H: HasDisplayHandle>(
         display_target: &H,
         physical_width: u32,
@@ -63,99 +537,282 @@ impl<A: AllocatorTrait> Integration<A> {
         swapchain_loader: Swapchain,
         swapchain: vk::SwapchainKHR,
         surface_format: vk::SurfaceFormatKHR,
-    ) -> Self {
+    ) -> Result<Self, IntegrationError> {
+        IntegrationDescriptor::new(
+            physical_width,
+            physical_height,
+            scale_factor,
+            device,
+            allocator,
+            qfi,
+            queue,
+        )
+        .swapchain(display_target, swapchain_loader, swapchain, surface_format)
+        .max_texture_side(max_texture_side)
+        .font_definitions(font_definitions)
+        .style(style)
+        .build()
+    }
+
+    /// Create an instance of the integration that uses dynamic rendering instead of a render pass.
+    ///
+    /// No `vk::RenderPass` or framebuffers are created.
+    /// Use [`Self::paint`] to draw into a swapchain image or [`Self::paint_with_image_view`] to draw into any image view.
+    ///
+    /// See [`IntegrationDescriptor`] for more settings.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_dynamic_rendering<H: HasDisplayHandle>(
+        display_target: &H,
+        physical_width: u32,
+        physical_height: u32,
+        scale_factor: f64,
+        max_texture_side: usize,
+        font_definitions: egui::FontDefinitions,
+        style: egui::Style,
+        device: Device,
+        allocator: A,
+        qfi: u32,
+        queue: vk::Queue,
+        swapchain_loader: Swapchain,
+        swapchain: vk::SwapchainKHR,
+        surface_format: vk::SurfaceFormatKHR,
+    ) -> Result<Self, IntegrationError> {
+        IntegrationDescriptor::new(
+            physical_width,
+            physical_height,
+            scale_factor,
+            device,
+            allocator,
+            qfi,
+            queue,
+        )
+        .swapchain(display_target, swapchain_loader, swapchain, surface_format)
+        .max_texture_side(max_texture_side)
+        .font_definitions(font_definitions)
+        .style(style)
+        .rendering_mode(RenderingMode::DynamicRendering)
+        .build()
+    }
+
+    /// Create an instance of the integration without a window, e.g. for tests on a software Vulkan driver.
+    ///
+    /// The integration renders into an offscreen image of `format` and the physical size owned by itself,
+    /// see [`Self::offscreen_image`], which [`Self::paint`] draws to with a `swapchain_image_index` of 0.
+    /// By default the image is cleared and left in `TRANSFER_SRC_OPTIMAL` layout, with one frame in flight.
+    /// Pass input with [`Self::begin_frame_with_input`] and end frames with [`Self::end_frame_headless`].
+    ///
+    /// See [`IntegrationDescriptor::headless`] for more settings.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_headless(
+        physical_width: u32,
+        physical_height: u32,
+        scale_factor: f64,
+        max_texture_side: usize,
+        font_definitions: egui::FontDefinitions,
+        style: egui::Style,
+        device: Device,
+        allocator: A,
+        qfi: u32,
+        queue: vk::Queue,
+        format: vk::Format,
+        rendering_mode: RenderingMode,
+    ) -> Result<Self, IntegrationError> {
+        IntegrationDescriptor::new(
+            physical_width,
+            physical_height,
+            scale_factor,
+            device,
+            allocator,
+            qfi,
+            queue,
+        )
+        .headless(format)
+        .max_texture_side(max_texture_side)
+        .font_definitions(font_definitions)
+        .style(style)
+        .rendering_mode(rendering_mode)
+        .build()
+    }
+
+    pub(crate) fn from_descriptor(
+        descriptor: IntegrationDescriptor<'_, A>,
+    ) -> Result<Self, IntegrationError> {
+        let target_desc = descriptor.resolved_target_desc();
+        let IntegrationDescriptor {
+            display_target,
+            swapchain,
+            format,
+            physical_width,
+            physical_height,
+            scale_factor,
+            device,
+            allocator,
+            qfi,
+            queue,
+            max_texture_side,
+            font_definitions,
+            style,
+            rendering_mode,
+            target_desc: _,
+            sample_count,
+            frames_in_flight,
+            shrink_buffers,
+            vertex_buffer_size,
+            index_buffer_size,
+            staging_buffer_size,
+            descriptor_pool_size,
+            destroy_on_drop,
+        } = descriptor;
+        let format = format.ok_or(IntegrationError::Misuse(
+            "Either a swapchain or a headless format is required",
+        ))?;
+        let buffer_sizes = BufferSizes {
+            vertex: vertex_buffer_size,
+            index: index_buffer_size,
+            staging: staging_buffer_size,
+        };
+
         // Create context
         let context = Context::default();
         context.set_fonts(font_definitions);
         context.set_style(style);
 
-        let egui_winit = egui_winit::State::new(
-            context.clone(),
-            context.viewport_id(),
-            display_target,
-            Some(scale_factor as f32),
-            Some(max_texture_side as usize),
-        );
+        let egui_winit = display_target.map(|display_target| {
+            egui_winit::State::new(
+                context.clone(),
+                context.viewport_id(),
+                display_target,
+                Some(scale_factor as f32),
+                Some(max_texture_side),
+            )
+        });
 
-        // Get swap_images to get len of swapchain images and to create framebuffers
-        let swap_images = unsafe {
-            swapchain_loader
-                .get_swapchain_images(swapchain)
-                .expect("Failed to get swapchain images.")
-        };
+        let (swapchain_loader, swapchain) = swapchain.unzip();
+        let mut integration = Self {
+            physical_width,
+            physical_height,
+            scale_factor,
+            context,
+            egui_winit,
+            max_texture_side,
+            viewports: AHashMap::new(),
 
-        // Create DescriptorPool
-        let descriptor_pool = unsafe {
-            device.create_descriptor_pool(
-                &vk::DescriptorPoolCreateInfo::default()
-                    .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
-                    .max_sets(1024)
-                    .pool_sizes(&[vk::DescriptorPoolSize::default()
-                        .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                        .descriptor_count(1024)]),
-                None,
-            )
+            device,
+            allocator,
+            qfi,
+            queue,
+            swapchain_loader,
+            offscreen_image: None,
+            descriptor_pools: DescriptorPools::new(descriptor_pool_size),
+            descriptor_set_layouts: vec![],
+            pipeline_layout: vk::PipelineLayout::null(),
+            pipeline: vk::Pipeline::null(),
+            samplers: AHashMap::new(),
+            rendering_mode,
+            target_desc,
+            render_pass: vk::RenderPass::null(),
+            swapchain_format: format,
+            swapchain_images: vec![],
+            framebuffer_color_image_views: vec![],
+            framebuffers: vec![],
+            render_passes: AHashMap::new(),
+            target_pipelines: AHashMap::new(),
+            target_framebuffers: AHashMap::new(),
+            sample_count,
+            msaa_images: AHashMap::new(),
+            buffer_sizes,
+            frame_buffers: vec![],
+            transfer_queue: None,
+            timeline: None,
+            screenshot_requested: false,
+            screenshot: None,
+            frame_index: 0,
+            frame_counter: 0,
+            manual_frame_index: false,
+            shrink_buffers,
+            destroy_on_drop,
+            destroyed: false,
+            texture_desc_sets: AHashMap::new(),
+            texture_images: AHashMap::new(),
+            texture_allocations: AHashMap::new(),
+            texture_image_views: AHashMap::new(),
+
+            user_texture_layout: vk::DescriptorSetLayout::null(),
+            user_textures: UserTextures::default(),
+
+            callback_resources: CallbackResources::default(),
+        };
+        // The vk objects are created into the integration, so that those created before a failure are destroyed
+        if let Err(err) = integration.create_objects(swapchain, frames_in_flight) {
+            unsafe { integration.destroy() }.ok();
+            return Err(err);
         }
-        .expect("Failed to create descriptor pool.");
+        Ok(integration)
+    }
 
-        // Create DescriptorSetLayouts
-        let descriptor_set_layouts = {
-            let mut sets = vec![];
-            for _ in 0..swap_images.len() {
-                sets.push(
-                    unsafe {
-                        device.create_descriptor_set_layout(
-                            &vk::DescriptorSetLayoutCreateInfo::default().bindings(&[
-                                vk::DescriptorSetLayoutBinding::default()
-                                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                                    .descriptor_count(1)
-                                    .binding(0)
-                                    .stage_flags(vk::ShaderStageFlags::FRAGMENT),
-                            ]),
-                            None,
-                        )
-                    }
-                    .expect("Failed to create descriptor set layout."),
-                );
+    // Create the vk objects of a new integration, which paints to the images of `swapchain` or to an offscreen image.
+    fn create_objects(
+        &mut self,
+        swapchain: Option<vk::SwapchainKHR>,
+        frames_in_flight: Option<usize>,
+    ) -> Result<(), IntegrationError> {
+        // Get swap_images to get len of swapchain images and to create framebuffers,
+        // or create the image headless integrations render to instead
+        self.swapchain_images = match (&self.swapchain_loader, swapchain) {
+            (Some(swapchain_loader), Some(swapchain)) => unsafe {
+                swapchain_loader.get_swapchain_images(swapchain)?
+            },
+            _ => {
+                let offscreen_image = create_attachment_image(
+                    &self.device,
+                    &self.allocator,
+                    self.swapchain_format,
+                    vk::Extent2D::default()
+                        .width(self.physical_width)
+                        .height(self.physical_height),
+                    vk::SampleCountFlags::TYPE_1,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT
+                        | vk::ImageUsageFlags::TRANSFER_SRC
+                        | vk::ImageUsageFlags::SAMPLED,
+                )?;
+                let image = offscreen_image.image;
+                self.offscreen_image = Some(offscreen_image);
+                vec![image]
             }
-            sets
         };
 
+        // Create DescriptorSetLayouts
+        for _ in 0..self.swapchain_images.len() {
+            let descriptor_set_layout = unsafe {
+                self.device.create_descriptor_set_layout(
+                    &vk::DescriptorSetLayoutCreateInfo::default().bindings(&[
+                        vk::DescriptorSetLayoutBinding::default()
+                            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                            .descriptor_count(1)
+                            .binding(0)
+                            .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+                    ]),
+                    None,
+                )
+            }?;
+            self.descriptor_set_layouts.push(descriptor_set_layout);
+        }
+
         // Create RenderPass
-        let render_pass = unsafe {
-            device.create_render_pass(
-                &vk::RenderPassCreateInfo::default()
-                    .attachments(&[vk::AttachmentDescription::default()
-                        .format(surface_format.format)
-                        .samples(vk::SampleCountFlags::TYPE_1)
-                        .load_op(vk::AttachmentLoadOp::LOAD)
-                        .store_op(vk::AttachmentStoreOp::STORE)
-                        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-                        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-                        .initial_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-                        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)])
-                    .subpasses(&[vk::SubpassDescription::default()
-                        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-                        .color_attachments(&[vk::AttachmentReference::default()
-                            .attachment(0)
-                            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)])])
-                    .dependencies(&[vk::SubpassDependency::default()
-                        .src_subpass(vk::SUBPASS_EXTERNAL)
-                        .dst_subpass(0)
-                        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-                        .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-                        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-                        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)]),
-                None,
-            )
+        if self.rendering_mode == RenderingMode::RenderPass {
+            self.render_pass = self.cached_render_pass(
+                self.swapchain_format,
+                self.target_desc.load.load_op(),
+                self.target_desc.initial_layout,
+                self.target_desc.final_layout,
+            )?;
         }
-        .expect("Failed to create render pass.");
 
         // Create PipelineLayout
-        let pipeline_layout = unsafe {
-            device.create_pipeline_layout(
+        self.pipeline_layout = unsafe {
+            self.device.create_pipeline_layout(
                 &vk::PipelineLayoutCreateInfo::default()
-                    .set_layouts(&descriptor_set_layouts)
+                    .set_layouts(&self.descriptor_set_layouts)
                     .push_constant_ranges(&[
                         vk::PushConstantRange::default()
                             .stage_flags(vk::ShaderStageFlags::VERTEX)
@@ -164,491 +821,1250 @@ impl<A: AllocatorTrait> Integration<A> {
                     ]),
                 None,
             )
-        }
-        .expect("Failed to create pipeline layout.");
+        }?;
 
         // Create Pipeline
-        let pipeline = {
-            let bindings = [vk::VertexInputBindingDescription::default()
-                .binding(0)
-                .input_rate(vk::VertexInputRate::VERTEX)
-                .stride(
-                    4 * std::mem::size_of::<f32>() as u32 + 4 * std::mem::size_of::<u8>() as u32,
-                )];
-
-            let attributes = [
-                // position
-                vk::VertexInputAttributeDescription::default()
-                    .binding(0)
-                    .offset(0)
-                    .location(0)
-                    .format(vk::Format::R32G32_SFLOAT),
-                // uv
-                vk::VertexInputAttributeDescription::default()
-                    .binding(0)
-                    .offset(8)
-                    .location(1)
-                    .format(vk::Format::R32G32_SFLOAT),
-                // color
-                vk::VertexInputAttributeDescription::default()
-                    .binding(0)
-                    .offset(16)
-                    .location(2)
-                    .format(vk::Format::R8G8B8A8_UNORM),
-            ];
-
-            let vertex_shader_module = {
-                let bytes_code = include_bytes!("shaders/spv/vert.spv");
-                let shader_module_create_info = vk::ShaderModuleCreateInfo {
-                    code_size: bytes_code.len(),
-                    p_code: bytes_code.as_ptr() as *const u32,
-                    ..Default::default()
-                };
-                unsafe { device.create_shader_module(&shader_module_create_info, None) }
-                    .expect("Failed to create vertex shader module.")
-            };
-            let fragment_shader_module = {
-                let bytes_code = include_bytes!("shaders/spv/frag.spv");
-                let shader_module_create_info = vk::ShaderModuleCreateInfo {
-                    code_size: bytes_code.len(),
-                    p_code: bytes_code.as_ptr() as *const u32,
-                    ..Default::default()
-                };
-                unsafe { device.create_shader_module(&shader_module_create_info, None) }
-                    .expect("Failed to create fragment shader module.")
-            };
-            let main_function_name = CString::new("main").unwrap();
-            let pipeline_shader_stages = [
-                vk::PipelineShaderStageCreateInfo::default()
-                    .stage(vk::ShaderStageFlags::VERTEX)
-                    .module(vertex_shader_module)
-                    .name(&main_function_name),
-                vk::PipelineShaderStageCreateInfo::default()
-                    .stage(vk::ShaderStageFlags::FRAGMENT)
-                    .module(fragment_shader_module)
-                    .name(&main_function_name),
-            ];
-
-            let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::default()
-                .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
-            let viewport_info = vk::PipelineViewportStateCreateInfo::default()
-                .viewport_count(1)
-                .scissor_count(1);
-            let rasterization_info = vk::PipelineRasterizationStateCreateInfo::default()
-                .depth_clamp_enable(false)
-                .rasterizer_discard_enable(false)
-                .polygon_mode(vk::PolygonMode::FILL)
-                .cull_mode(vk::CullModeFlags::NONE)
-                .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-                .depth_bias_enable(false)
-                .line_width(1.0);
-            let stencil_op = vk::StencilOpState::default()
-                .fail_op(vk::StencilOp::KEEP)
-                .pass_op(vk::StencilOp::KEEP)
-                .compare_op(vk::CompareOp::ALWAYS);
-            let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::default()
-                .depth_test_enable(false)
-                .depth_write_enable(false)
-                .depth_compare_op(vk::CompareOp::ALWAYS)
-                .depth_bounds_test_enable(false)
-                .stencil_test_enable(false)
-                .front(stencil_op)
-                .back(stencil_op);
-            let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::default()
-                .color_write_mask(
-                    vk::ColorComponentFlags::R
-                        | vk::ColorComponentFlags::G
-                        | vk::ColorComponentFlags::B
-                        | vk::ColorComponentFlags::A,
-                )
-                .blend_enable(true)
-                .src_color_blend_factor(vk::BlendFactor::ONE)
-                .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)];
-            let color_blend_info = vk::PipelineColorBlendStateCreateInfo::default()
-                .attachments(&color_blend_attachments);
-            let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-            let dynamic_state_info =
-                vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
-            let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default()
-                .vertex_attribute_descriptions(&attributes)
-                .vertex_binding_descriptions(&bindings);
-            let multisample_info = vk::PipelineMultisampleStateCreateInfo::default()
-                .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-
-            let pipeline_create_info = [vk::GraphicsPipelineCreateInfo::default()
-                .stages(&pipeline_shader_stages)
-                .vertex_input_state(&vertex_input_state)
-                .input_assembly_state(&input_assembly_info)
-                .viewport_state(&viewport_info)
-                .rasterization_state(&rasterization_info)
-                .multisample_state(&multisample_info)
-                .depth_stencil_state(&depth_stencil_info)
-                .color_blend_state(&color_blend_info)
-                .dynamic_state(&dynamic_state_info)
-                .layout(pipeline_layout)
-                .render_pass(render_pass)
-                .subpass(0)];
-
-            let pipeline = unsafe {
-                device.create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &pipeline_create_info,
-                    None,
-                )
-            }
-            .expect("Failed to create graphics pipeline.")[0];
-            unsafe {
-                device.destroy_shader_module(vertex_shader_module, None);
-                device.destroy_shader_module(fragment_shader_module, None);
-            }
-            pipeline
-        };
+        self.pipeline = create_pipeline(
+            &self.device,
+            self.pipeline_layout,
+            self.render_pass,
+            self.swapchain_format,
+            self.sample_count,
+        )?;
+
+        // Create color image views; the framebuffers need them and may need multisampled attachments
+        self.framebuffer_color_image_views = create_swapchain_image_views(
+            &self.device,
+            &self.swapchain_images,
+            self.swapchain_format,
+        )?;
+        self.framebuffers = self.create_swapchain_framebuffers()?;
+
+        // Create vertex buffer and index buffer for each frame in flight
+        for _ in 0..frames_in_flight.unwrap_or(self.swapchain_images.len()) {
+            let frame = FrameBuffers::new(&self.device, &self.allocator, self.buffer_sizes, &[])?;
+            self.frame_buffers.push(frame);
+        }
 
-        // Create Sampler
-        let sampler = unsafe {
-            device.create_sampler(
-                &vk::SamplerCreateInfo::default()
-                    .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
-                    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
-                    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
-                    .anisotropy_enable(false)
-                    .min_filter(vk::Filter::LINEAR)
-                    .mag_filter(vk::Filter::LINEAR)
-                    .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
-                    .min_lod(0.0)
-                    .max_lod(vk::LOD_CLAMP_NONE),
+        // User Textures
+        self.user_texture_layout = unsafe {
+            self.device.create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::default().bindings(&[
+                    vk::DescriptorSetLayoutBinding::default()
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .descriptor_count(1)
+                        .binding(0)
+                        .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+                ]),
                 None,
             )
+        }?;
+        Ok(())
+    }
+
+    /// handling winit event.
+    pub fn handle_event(
+        &mut self,
+        window: &Window,
+        winit_event: &egui_winit::winit::event::WindowEvent,
+    ) -> Result<EventResponse, IntegrationError> {
+        self.check_not_destroyed()?;
+        if let egui_winit::winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. } =
+            winit_event
+        {
+            self.scale_factor = *scale_factor;
         }
-        .expect("Failed to create sampler.");
 
-        // Create Framebuffers
-        let framebuffer_color_image_views = swap_images
-            .iter()
-            .map(|swapchain_image| unsafe {
-                device
-                    .create_image_view(
-                        &vk::ImageViewCreateInfo::default()
-                            .image(swapchain_image.clone())
-                            .view_type(vk::ImageViewType::TYPE_2D)
-                            .format(surface_format.format)
-                            .subresource_range(
-                                vk::ImageSubresourceRange::default()
-                                    .aspect_mask(vk::ImageAspectFlags::COLOR)
-                                    .base_mip_level(0)
-                                    .level_count(1)
-                                    .base_array_layer(0)
-                                    .layer_count(1),
-                            ),
-                        None,
-                    )
-                    .expect("Failed to create image view.")
-            })
-            .collect::<Vec<_>>();
-        let framebuffers = framebuffer_color_image_views
-            .iter()
-            .map(|&image_views| unsafe {
-                let attachments = &[image_views];
-                device
-                    .create_framebuffer(
-                        &vk::FramebufferCreateInfo::default()
-                            .render_pass(render_pass)
-                            .attachments(attachments)
-                            .width(physical_width)
-                            .height(physical_height)
-                            .layers(1),
-                        None,
-                    )
-                    .expect("Failed to create framebuffer.")
-            })
-            .collect::<Vec<_>>();
+        Ok(self
+            .egui_winit
+            .as_mut()
+            .ok_or(IntegrationError::Misuse(
+                "Headless integrations have no window",
+            ))?
+            .on_window_event(window, winit_event))
+    }
 
-        // Create vertex buffer and index buffer
-        let mut vertex_buffers = vec![];
-        let mut vertex_buffer_allocations = vec![];
-        let mut index_buffers = vec![];
-        let mut index_buffer_allocations = vec![];
-        for _ in 0..framebuffers.len() {
-            let vertex_buffer = unsafe {
-                device
-                    .create_buffer(
-                        &vk::BufferCreateInfo::default()
-                            .usage(vk::BufferUsageFlags::VERTEX_BUFFER)
-                            .sharing_mode(vk::SharingMode::EXCLUSIVE)
-                            .size(Self::vertex_buffer_size()),
-                        None,
-                    )
-                    .expect("Failed to create vertex buffer.")
-            };
-            let vertex_buffer_requirements =
-                unsafe { device.get_buffer_memory_requirements(vertex_buffer) };
-            let vertex_buffer_allocation = allocator
-                .allocate(A::AllocationCreateInfo::new(
-                    vertex_buffer_requirements,
-                    MemoryLocation::CpuToGpu,
-                    true,
-                ))
-                .expect("Failed to create vertex buffer.");
+    /// begin frame.
+    ///
+    /// Unless [`Self::set_frame_index`] has been called, this also moves on to the buffers of the next frame in flight.
+    pub fn begin_frame(&mut self, window: &Window) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        let raw_input = self
+            .egui_winit
+            .as_mut()
+            .ok_or(IntegrationError::Misuse(
+                "Headless integrations have no window",
+            ))?
+            .take_egui_input(window);
+        self.begin_frame_with_input(raw_input)
+    }
+
+    /// begin frame with input gathered by the application instead of from winit events.
+    ///
+    /// The screen rect and native pixels per point default to the physical size and scale factor.
+    /// Like [`Self::begin_frame`], this moves on to the buffers of the next frame in flight.
+    pub fn begin_frame_with_input(
+        &mut self,
+        mut raw_input: egui::RawInput,
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        if !self.manual_frame_index {
+            self.frame_index = (self.frame_index + 1) % self.frame_buffers.len();
+            self.frame_counter += 1;
+        }
+        if let Some(readback) = self.screenshot.take() {
             unsafe {
-                device
-                    .bind_buffer_memory(
-                        vertex_buffer,
-                        vertex_buffer_allocation.memory(),
-                        vertex_buffer_allocation.offset(),
-                    )
-                    .expect("Failed to create vertex buffer.")
+                self.device.queue_wait_idle(self.queue)?;
             }
+            raw_input.events.push(egui::Event::Screenshot {
+                viewport_id: ViewportId::ROOT,
+                image: Arc::new(self.finish_readback(readback)?),
+            });
+        }
+        raw_input.screen_rect.get_or_insert_with(|| {
+            egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(self.physical_width as f32, self.physical_height as f32)
+                    / self.scale_factor as f32,
+            )
+        });
+        raw_input
+            .viewports
+            .entry(raw_input.viewport_id)
+            .or_default()
+            .native_pixels_per_point
+            .get_or_insert(self.scale_factor as f32);
+        self.context.begin_frame(raw_input);
+        Ok(())
+    }
 
-            let index_buffer = unsafe {
-                device
-                    .create_buffer(
-                        &vk::BufferCreateInfo::default()
-                            .usage(vk::BufferUsageFlags::INDEX_BUFFER)
-                            .sharing_mode(vk::SharingMode::EXCLUSIVE)
-                            .size(Self::index_buffer_size()),
-                        None,
-                    )
-                    .expect("Failed to create index buffer.")
-            };
-            let index_buffer_requirements =
-                unsafe { device.get_buffer_memory_requirements(index_buffer) };
-            let index_buffer_allocation = allocator
-                .allocate(A::AllocationCreateInfo::new(
-                    index_buffer_requirements,
-                    MemoryLocation::CpuToGpu,
-                    true,
-                ))
-                .expect("Failed to create index buffer.");
+    /// end frame.
+    pub fn end_frame(&mut self, window: &Window) -> Result<egui::FullOutput, IntegrationError> {
+        self.check_not_destroyed()?;
+        let egui_winit = self.egui_winit.as_mut().ok_or(IntegrationError::Misuse(
+            "Headless integrations have no window",
+        ))?;
+        let output = self.context.end_frame();
+        egui_winit.handle_platform_output(window, output.platform_output.clone());
+        self.check_screenshot_request(&output);
+
+        Ok(output)
+    }
+
+    /// end frame without a window, leaving the platform output to the application.
+    pub fn end_frame_headless(&mut self) -> egui::FullOutput {
+        let output = self.context.end_frame();
+        self.check_screenshot_request(&output);
+        output
+    }
+
+    // Remember a `ViewportCommand::Screenshot` of the root viewport for the next paint.
+    fn check_screenshot_request(&mut self, output: &egui::FullOutput) {
+        if let Some(viewport_output) = output.viewport_output.get(&ViewportId::ROOT) {
+            if viewport_output
+                .commands
+                .contains(&egui::ViewportCommand::Screenshot)
+            {
+                self.screenshot_requested = true;
+            }
+        }
+    }
+
+    /// Get the image headless integrations render to, and a view of it.
+    ///
+    /// Returns `None` for integrations created with a swapchain.
+    pub fn offscreen_image(&self) -> Option<(vk::Image, vk::ImageView)> {
+        self.offscreen_image
+            .as_ref()
+            .map(|offscreen_image| (offscreen_image.image, offscreen_image.image_view))
+    }
+
+    /// Get [`egui::Context`].
+    pub fn context(&self) -> Context {
+        self.context.clone()
+    }
+
+    /// Set the number of frames that may be in flight on the GPU at once.
+    ///
+    /// Each frame in flight gets its own vertex and index buffers, which are reused once the frame
+    /// `frames_in_flight` frames earlier has finished. Defaults to the number of swapchain images at creation.
+    /// The GPU must not be using any buffers of the integration when this is called.
+    pub fn set_frames_in_flight(
+        &mut self,
+        frames_in_flight: usize,
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        if frames_in_flight == 0 {
+            return Err(IntegrationError::Misuse(
+                "frames_in_flight must be at least 1",
+            ));
+        }
+        while self.frame_buffers.len() > frames_in_flight {
+            let frame = self.frame_buffers.pop().unwrap();
             unsafe {
-                device
-                    .bind_buffer_memory(
-                        index_buffer,
-                        index_buffer_allocation.memory(),
-                        index_buffer_allocation.offset(),
-                    )
-                    .expect("Failed to create index buffer.")
+                frame.destroy(&self.device, &self.allocator, &mut self.descriptor_pools)?;
             }
+        }
+        let staging_queue_family_indices = self.staging_queue_family_indices();
+        while self.frame_buffers.len() < frames_in_flight {
+            self.frame_buffers.push(FrameBuffers::new(
+                &self.device,
+                &self.allocator,
+                self.buffer_sizes,
+                &staging_queue_family_indices,
+            )?);
+        }
+        self.frame_index %= frames_in_flight;
+        Ok(())
+    }
 
-            vertex_buffers.push(vertex_buffer);
-            vertex_buffer_allocations.push(vertex_buffer_allocation);
-            index_buffers.push(index_buffer);
-            index_buffer_allocations.push(index_buffer_allocation);
+    /// Select the frame in flight used by the following paint calls, e.g. the index of the frame's fence.
+    ///
+    /// `frame_index` must be less than the number of frames in flight, and the GPU must have finished
+    /// the previous frame painted with the same index. Once this is called, [`Self::begin_frame`]
+    /// no longer advances the frame index, so call it every frame.
+    pub fn set_frame_index(&mut self, frame_index: usize) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        if frame_index >= self.frame_buffers.len() {
+            return Err(IntegrationError::Misuse(
+                "frame_index must be less than the number of frames in flight",
+            ));
         }
+        self.manual_frame_index = true;
+        self.frame_index = frame_index;
+        self.frame_counter += 1;
+        Ok(())
+    }
 
-        // User Textures
-        let user_texture_layout = unsafe {
-            device.create_descriptor_set_layout(
-                &vk::DescriptorSetLayoutCreateInfo::default().bindings(&[
-                    vk::DescriptorSetLayoutBinding::default()
-                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                        .descriptor_count(1)
-                        .binding(0)
-                        .stage_flags(vk::ShaderStageFlags::FRAGMENT),
-                ]),
-                None,
-            )
+    /// Upload new textures on a queue of a separate family, e.g. a dedicated transfer queue.
+    ///
+    /// Uploads are submitted to `queue` during the paint calls and handed over to the graphics queue family.
+    /// The graphics submit of each frame must then wait on [`Self::upload_semaphores`] at the
+    /// `TRANSFER` stage. Partial updates of existing textures are still recorded into the paint command buffer.
+    /// The GPU must not be using a transfer queue set before when this is called.
+    pub fn set_transfer_queue(
+        &mut self,
+        queue_family_index: u32,
+        queue: vk::Queue,
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        if queue_family_index == self.qfi {
+            return Err(IntegrationError::Misuse(
+                "The transfer queue must be of a different family than the graphics queue",
+            ));
+        }
+        if let Some(transfer_queue) = self.transfer_queue.take() {
+            unsafe {
+                transfer_queue.destroy(&self.device);
+            }
         }
-        .expect("Failed to create descriptor set layout.");
-        let user_textures = vec![];
+        self.transfer_queue = Some(TransferQueue {
+            queue_family_index,
+            queue,
+            frames: vec![],
+        });
+
+        // Both queue families use the staging buffers from now on
+        let staging_queue_family_indices = self.staging_queue_family_indices();
+        for frame in &mut self.frame_buffers {
+            let retired = frame.staging_buffer.share(
+                &self.device,
+                &self.allocator,
+                &staging_queue_family_indices,
+            )?;
+            frame.retired_buffers.push(retired);
+            frame.staging_offset = 0;
+        }
+        Ok(())
+    }
 
-        Self {
-            physical_width,
-            physical_height,
-            scale_factor,
-            context,
-            egui_winit,
+    // Get the queue families the staging buffers are shared by, none if uploads only use the graphics queue.
+    fn staging_queue_family_indices(&self) -> Vec<u32> {
+        match &self.transfer_queue {
+            Some(transfer_queue) => vec![self.qfi, transfer_queue.queue_family_index],
+            None => vec![],
+        }
+    }
 
-            device,
-            allocator,
-            qfi,
-            queue,
-            swapchain_loader,
-            descriptor_pool,
-            descriptor_set_layouts,
-            pipeline_layout,
-            pipeline,
-            sampler,
-            render_pass,
-            framebuffer_color_image_views,
-            framebuffers,
-            vertex_buffers,
-            vertex_buffer_allocations,
-            index_buffers,
-            index_buffer_allocations,
-            texture_desc_sets: AHashMap::new(),
-            texture_images: AHashMap::new(),
-            texture_image_infos: AHashMap::new(),
-            texture_allocations: AHashMap::new(),
-            texture_image_views: AHashMap::new(),
+    /// Get the semaphores signaled by the texture uploads of the current frame.
+    ///
+    /// Call this after the paint calls of a frame; the graphics submit of the frame must wait on all of them
+    /// with `vk::PipelineStageFlags::TRANSFER`. Always empty unless [`Self::set_transfer_queue`] was called.
+    pub fn upload_semaphores(&self) -> &[vk::Semaphore] {
+        self.transfer_queue
+            .as_ref()
+            .and_then(|transfer_queue| transfer_queue.frames.get(self.frame_index))
+            .map_or(&[], |frame| &frame.semaphores[..frame.submitted])
+    }
 
-            user_texture_layout,
-            user_textures,
+    /// Track the frames in flight with a timeline semaphore owned by the application.
+    ///
+    /// `value` is the current value of the semaphore. From the next frame on, the paint calls return the value
+    /// the graphics submit of the frame has to signal, and the integration waits for it before reusing the
+    /// frame's buffers and destroying the objects the frame used, instead of relying on [`Self::set_frame_index`]
+    /// or the swapchain loop. Requires the `timelineSemaphore` feature (Vulkan 1.2).
+    pub fn set_timeline_semaphore(&mut self, semaphore: vk::Semaphore, value: u64) {
+        self.timeline = Some((semaphore, value));
+        for frame in self.frame_buffers.iter_mut() {
+            frame.timeline_value = 0;
         }
     }
 
-    // vertex buffer size
-    fn vertex_buffer_size() -> u64 {
-        1024 * 1024 * 4
+    /// Set whether vertex, index and texture staging buffers are shrunk after a sustained period of low use.
+    ///
+    /// Buffers always grow as needed. Shrinking is disabled by default.
+    pub fn set_shrink_buffers(&mut self, shrink_buffers: bool) {
+        self.shrink_buffers = shrink_buffers;
     }
 
-    // index buffer size
-    fn index_buffer_size() -> u64 {
-        1024 * 1024 * 2
+    /// Set whether dropping the integration waits for its queues and destroys its vk objects.
+    ///
+    /// Enabled by default. Disable it if the application tears down the device itself; the objects are then leaked
+    /// unless [`Self::destroy`] is called.
+    pub fn set_destroy_on_drop(&mut self, destroy_on_drop: bool) {
+        self.destroy_on_drop = destroy_on_drop;
     }
 
-    /// handling winit event.
-    pub fn handle_event(
-        &mut self,
-        window: &Window,
-        winit_event: &egui_winit::winit::event::WindowEvent,
-    ) -> EventResponse {
-        if let egui_winit::winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. } =
-            winit_event
-        {
-            self.scale_factor = *scale_factor;
+    /// Get the resources shared with paint callbacks.
+    pub fn callback_resources(&self) -> &CallbackResources {
+        &self.callback_resources
+    }
+
+    /// Get the resources shared with paint callbacks mutably.
+    ///
+    /// Resources left here are destroyed in [`Self::destroy`].
+    pub fn callback_resources_mut(&mut self) -> &mut CallbackResources {
+        &mut self.callback_resources
+    }
+
+    /// Set how [`Self::paint`] loads and transitions the swapchain images.
+    ///
+    /// Defaults to [`TargetDesc::default`], which draws on top of an image in `COLOR_ATTACHMENT_OPTIMAL`
+    /// layout and leaves it in `PRESENT_SRC_KHR` layout.
+    pub fn set_target_desc(&mut self, target_desc: TargetDesc) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        self.target_desc = target_desc;
+        if self.rendering_mode == RenderingMode::RenderPass {
+            // The swapchain framebuffers and pipeline stay compatible, since only the load op and layouts change
+            let format = self.swapchain_format;
+            self.render_pass = self.cached_render_pass(
+                format,
+                target_desc.load.load_op(),
+                target_desc.initial_layout,
+                target_desc.final_layout,
+            )?;
         }
+        Ok(())
+    }
 
-        self.egui_winit.on_window_event(window, winit_event)
+    /// Copy an image into host memory, e.g. a [`PaintTarget`] or the offscreen image of a headless integration.
+    ///
+    /// The copy is submitted to the integration's queue and waited for, so call this after submitting the
+    /// commands that render the image. `layout` is the layout the image is in and is left in, and the image
+    /// must have been created with `TRANSFER_SRC` usage. 8-bit RGBA and BGRA formats are supported;
+    /// what egui paints into both `UNORM` and `SRGB` formats is stored gamma encoded like [`egui::Color32`].
+    pub fn read_image(
+        &mut self,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Result<egui::ColorImage, IntegrationError> {
+        self.check_not_destroyed()?;
+        check_readback(layout, format)?;
+        let command_pool = unsafe {
+            self.device.create_command_pool(
+                &vk::CommandPoolCreateInfo::default()
+                    .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                    .queue_family_index(self.qfi),
+                None,
+            )
+        }?;
+        let fence = match unsafe {
+            self.device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+        } {
+            Ok(fence) => fence,
+            Err(err) => {
+                unsafe { self.device.destroy_command_pool(command_pool, None) };
+                return Err(err.into());
+            }
+        };
+        let readback = self.submit_readback(command_pool, fence, image, layout, format, extent);
+        unsafe {
+            self.device.destroy_fence(fence, None);
+            self.device.destroy_command_pool(command_pool, None);
+        }
+        self.finish_readback(readback?)
     }
 
-    /// begin frame.
-    pub fn begin_frame(&mut self, window: &Window) {
-        let raw_input = self.egui_winit.take_egui_input(window);
-        self.context.begin_frame(raw_input);
+    // Record the readback of an image in a command buffer of `command_pool`, then submit it and wait for `fence`.
+    fn submit_readback(
+        &self,
+        command_pool: vk::CommandPool,
+        fence: vk::Fence,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Result<Readback<A>, IntegrationError> {
+        let command_buffer = unsafe {
+            self.device.allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo::default()
+                    .command_pool(command_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1),
+            )
+        }?[0];
+        unsafe {
+            self.device.begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )?;
+        }
+        let readback = self.record_readback(command_buffer, image, layout, format, extent)?;
+        let submitted = unsafe {
+            self.device
+                .end_command_buffer(command_buffer)
+                .and_then(|()| {
+                    self.device.queue_submit(
+                        self.queue,
+                        &[vk::SubmitInfo::default().command_buffers(&[command_buffer])],
+                        fence,
+                    )
+                })
+                .and_then(|()| self.device.wait_for_fences(&[fence], true, u64::MAX))
+        };
+        if let Err(err) = submitted {
+            unsafe { self.device.destroy_buffer(readback.buffer, None) };
+            self.allocator.free(readback.allocation).ok();
+            return Err(err.into());
+        }
+        Ok(readback)
     }
 
-    /// end frame.
-    pub fn end_frame(&mut self, window: &Window) -> egui::FullOutput {
-        let output = self.context.end_frame();
+    // Record the copy of an image in `layout` into a new host-visible buffer.
+    fn record_readback(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Result<Readback<A>, IntegrationError> {
+        check_readback(layout, format)?;
+        let buffer = unsafe {
+            self.device.create_buffer(
+                &vk::BufferCreateInfo::default()
+                    .usage(vk::BufferUsageFlags::TRANSFER_DST)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                    .size(extent.width as u64 * extent.height as u64 * 4),
+                None,
+            )
+        }?;
+        let requirements = unsafe { self.device.get_buffer_memory_requirements(buffer) };
+        let allocation = match self.allocator.allocate(A::AllocationCreateInfo::new(
+            requirements,
+            MemoryLocation::GpuToCpu,
+            true,
+        )) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                unsafe { self.device.destroy_buffer(buffer, None) };
+                return Err(err.into());
+            }
+        };
+        if let Err(err) = unsafe {
+            self.device
+                .bind_buffer_memory(buffer, allocation.memory(), allocation.offset())
+        } {
+            unsafe { self.device.destroy_buffer(buffer, None) };
+            self.allocator.free(allocation).ok();
+            return Err(err.into());
+        }
 
-        self.egui_winit
-            .handle_platform_output(window, output.platform_output.clone());
+        insert_image_memory_barrier(
+            &self.device,
+            &command_buffer,
+            &image,
+            vk::QUEUE_FAMILY_IGNORED,
+            vk::QUEUE_FAMILY_IGNORED,
+            vk::AccessFlags::MEMORY_WRITE,
+            vk::AccessFlags::TRANSFER_READ,
+            layout,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::PipelineStageFlags::TRANSFER,
+            color_subresource_range(),
+        );
+        unsafe {
+            self.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer,
+                &[vk::BufferImageCopy::default()
+                    .image_subresource(
+                        vk::ImageSubresourceLayers::default()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .mip_level(0)
+                            .base_array_layer(0)
+                            .layer_count(1),
+                    )
+                    .image_extent(vk::Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: 1,
+                    })],
+            );
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[vk::BufferMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::HOST_READ)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .buffer(buffer)
+                    .size(vk::WHOLE_SIZE)],
+                &[],
+            );
+        }
+        insert_image_memory_barrier(
+            &self.device,
+            &command_buffer,
+            &image,
+            vk::QUEUE_FAMILY_IGNORED,
+            vk::QUEUE_FAMILY_IGNORED,
+            vk::AccessFlags::TRANSFER_READ,
+            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            layout,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            color_subresource_range(),
+        );
 
-        output
+        Ok(Readback {
+            buffer,
+            allocation,
+            format,
+            extent,
+        })
     }
 
-    /// Get [`egui::Context`].
-    pub fn context(&self) -> Context {
-        self.context.clone()
+    // Convert a finished readback into an egui image and destroy its buffer.
+    fn finish_readback(&self, readback: Readback<A>) -> Result<egui::ColorImage, IntegrationError> {
+        let [r, g, b, a] = readback_swizzle(readback.format).unwrap();
+        let pixel_count = readback.extent.width as usize * readback.extent.height as usize;
+        let pixels = readback.allocation.mapped_ptr().map(|ptr| {
+            let data =
+                unsafe { std::slice::from_raw_parts(ptr.as_ptr() as *const u8, pixel_count * 4) };
+            data.chunks_exact(4)
+                .map(|p| egui::Color32::from_rgba_premultiplied(p[r], p[g], p[b], p[a]))
+                .collect()
+        });
+        unsafe {
+            self.device.destroy_buffer(readback.buffer, None);
+        }
+        self.allocator.free(readback.allocation)?;
+        let pixels = pixels.ok_or_else(|| {
+            IntegrationError::Allocator(anyhow::anyhow!(
+                "Readback buffer memory is not host visible"
+            ))
+        })?;
+        Ok(egui::ColorImage {
+            size: [
+                readback.extent.width as usize,
+                readback.extent.height as usize,
+            ],
+            pixels,
+        })
+    }
+
+    /// Set the number of samples egui is rendered with.
+    ///
+    /// With more than one sample, egui is drawn into a transient multisampled image managed by the integration,
+    /// which is resolved into the swapchain or target image. The count must be supported by the device for
+    /// color attachments, and the target load must then be [`AttachmentLoad::Clear`] or [`AttachmentLoad::DontCare`],
+    /// since a multisampled image cannot load the contents of the image it resolves into.
+    /// The GPU must not be using any pipelines or framebuffers of the integration when this is called.
+    pub fn set_sample_count(
+        &mut self,
+        sample_count: vk::SampleCountFlags,
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        if sample_count == self.sample_count {
+            return Ok(());
+        }
+        self.sample_count = sample_count;
+
+        // release vk objects to be regenerated.
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+            for (_, pipeline) in self.target_pipelines.drain() {
+                self.device.destroy_pipeline(pipeline, None);
+            }
+            for &framebuffer in self.framebuffers.iter() {
+                self.device.destroy_framebuffer(framebuffer, None);
+            }
+            for (_, cached) in self.target_framebuffers.drain() {
+                self.device.destroy_framebuffer(cached.framebuffer, None);
+            }
+            for viewport in self.viewports.values_mut() {
+                for framebuffer in viewport.framebuffers.drain(..) {
+                    self.device.destroy_framebuffer(framebuffer, None);
+                }
+            }
+            for (_, msaa_image) in self.msaa_images.drain() {
+                destroy_attachment_image(&self.device, &self.allocator, msaa_image)?;
+            }
+        }
+
+        if self.rendering_mode == RenderingMode::RenderPass {
+            self.render_pass = self.cached_render_pass(
+                self.swapchain_format,
+                self.target_desc.load.load_op(),
+                self.target_desc.initial_layout,
+                self.target_desc.final_layout,
+            )?;
+        }
+        self.pipeline = create_pipeline(
+            &self.device,
+            self.pipeline_layout,
+            self.render_pass,
+            self.swapchain_format,
+            self.sample_count,
+        )?;
+        self.framebuffers = self.create_swapchain_framebuffers()?;
+        for viewport_id in self.viewports.keys().copied().collect::<Vec<_>>() {
+            self.recreate_viewport_framebuffers(viewport_id)?;
+        }
+        Ok(())
     }
 
     /// Record paint commands.
+    ///
+    /// `swapchain_image_index` selects the swapchain image to draw to. The image is loaded and
+    /// transitioned as described by [`Self::set_target_desc`].
+    /// Returns the timeline value to signal if [`Self::set_timeline_semaphore`] has been called.
+    ///
+    /// If egui requested a screenshot, the image is also copied and delivered as an [`egui::Event::Screenshot`]
+    /// at the start of the next frame; the swapchain must then have been created with `TRANSFER_SRC` usage.
     pub fn paint(
         &mut self,
         command_buffer: vk::CommandBuffer,
         swapchain_image_index: usize,
         clipped_meshes: Vec<egui::ClippedPrimitive>,
         textures_delta: TexturesDelta,
-    ) {
-        let index = swapchain_image_index;
-
-        for (id, image_delta) in textures_delta.set {
-            self.update_texture(id, image_delta);
-        }
-
-        let mut vertex_buffer_ptr = self.vertex_buffer_allocations[index]
-            .mapped_ptr()
-            .unwrap()
-            .as_ptr() as *mut u8;
-        // let mut vertex_buffer_ptr = unsafe {
-        //     self.device
-        //         .map_memory(
-        //             self.vertex_buffer_allocations[index].memory(),
-        //             self.vertex_buffer_allocations[index].offset(),
-        //             self.vertex_buffer_allocations[index].size(),
-        //             vk::MemoryMapFlags::empty(),
-        //         )
-        //         .expect("Failed to map buffers.") as *mut u8
-        // };
-        let vertex_buffer_ptr_end =
-            unsafe { vertex_buffer_ptr.add(Self::vertex_buffer_size() as usize) };
-        // let mut index_buffer_ptr = unsafe {
-        //     self.device
-        //         .map_memory(
-        //             self.index_buffer_allocations[index].memory(),
-        //             self.index_buffer_allocations[index].offset(),
-        //             self.index_buffer_allocations[index].size(),
-        //             vk::MemoryMapFlags::empty(),
-        //         )
-        //         .expect("Failed to map buffers.") as *mut u8
-        // };
-        let mut index_buffer_ptr = self.index_buffer_allocations[index]
-            .mapped_ptr()
-            .unwrap()
-            .as_ptr() as *mut u8;
-        let index_buffer_ptr_end =
-            unsafe { index_buffer_ptr.add(Self::index_buffer_size() as usize) };
+    ) -> Result<Option<TimelineSignal>, IntegrationError> {
+        self.check_not_destroyed()?;
+        let target = self.swapchain_target(swapchain_image_index)?;
+        let image = self.swapchain_images[swapchain_image_index];
+        let signal =
+            self.paint_target(command_buffer, None, target, clipped_meshes, textures_delta)?;
+        self.take_screenshot(
+            command_buffer,
+            image,
+            self.target_desc.final_layout,
+            self.swapchain_format,
+            self.swapchain_extent(),
+        )?;
+        Ok(signal)
+    }
 
-        // begin render pass
+    // Copy the image just painted if egui requested a screenshot.
+    fn take_screenshot(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Result<(), IntegrationError> {
+        if self.screenshot_requested && self.screenshot.is_none() {
+            self.screenshot_requested = false;
+            self.screenshot =
+                Some(self.record_readback(command_buffer, image, layout, format, extent)?);
+        }
+        Ok(())
+    }
+
+    /// Record the draw commands for a swapchain image into a secondary command buffer.
+    ///
+    /// Texture uploads are recorded into `command_buffer`, the primary command buffer, which must be
+    /// outside of a render pass; `secondary_command_buffer` is begun with the inheritance info of the
+    /// render pass and framebuffer, or of dynamic rendering, and ended. Execute it from `command_buffer`
+    /// between [`Self::begin_secondary_rendering`] and [`Self::end_secondary_rendering`].
+    pub fn paint_secondary(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        secondary_command_buffer: vk::CommandBuffer,
+        swapchain_image_index: usize,
+        clipped_meshes: Vec<egui::ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<Option<TimelineSignal>, IntegrationError> {
+        self.check_not_destroyed()?;
+        let target = self.swapchain_target(swapchain_image_index)?;
+        self.paint_target(
+            command_buffer,
+            Some(secondary_command_buffer),
+            target,
+            clipped_meshes,
+            textures_delta,
+        )
+    }
+
+    /// Begin the render pass or dynamic rendering for a swapchain image in `command_buffer`,
+    /// with the contents provided by secondary command buffers recorded by [`Self::paint_secondary`].
+    pub fn begin_secondary_rendering(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        swapchain_image_index: usize,
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        let target = self.swapchain_target(swapchain_image_index)?;
+        self.begin_rendering(command_buffer, &target, true);
+        Ok(())
+    }
+
+    /// End the render pass or dynamic rendering begun by [`Self::begin_secondary_rendering`].
+    ///
+    /// If egui requested a screenshot, the swapchain image is copied afterwards as in [`Self::paint`].
+    pub fn end_secondary_rendering(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        swapchain_image_index: usize,
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        let target = self.swapchain_target(swapchain_image_index)?;
+        self.end_rendering(command_buffer, &target);
+        self.take_screenshot(
+            command_buffer,
+            self.swapchain_images[swapchain_image_index],
+            self.target_desc.final_layout,
+            self.swapchain_format,
+            self.swapchain_extent(),
+        )
+    }
+
+    /// Record paint commands into `image_view` with dynamic rendering.
+    ///
+    /// Only available for integrations using [`RenderingMode::DynamicRendering`].
+    /// The image must be in `COLOR_ATTACHMENT_OPTIMAL` layout and have the surface format and size;
+    /// it is left in that layout. The load behavior of [`Self::set_target_desc`] applies.
+    /// Screenshots requested by egui are left to the next paint call that knows the image, since only its
+    /// view is passed here.
+    pub fn paint_with_image_view(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image_view: vk::ImageView,
+        clipped_meshes: Vec<egui::ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<Option<TimelineSignal>, IntegrationError> {
+        self.check_not_destroyed()?;
+        if self.rendering_mode != RenderingMode::DynamicRendering {
+            return Err(IntegrationError::Misuse(
+                "paint_with_image_view requires RenderingMode::DynamicRendering",
+            ));
+        }
+        let target = RenderTarget {
+            extent: self.swapchain_extent(),
+            format: self.swapchain_format,
+            pixels_per_point: self.scale_factor as f32,
+            pipeline: self.pipeline,
+            load: self.target_desc.load,
+            attachment: TargetAttachment::ImageView {
+                image_view,
+                msaa: self.msaa_attachment(self.swapchain_format, self.swapchain_extent())?,
+                transition: None,
+            },
+        };
+        self.paint_target(command_buffer, None, target, clipped_meshes, textures_delta)
+    }
+
+    /// Record paint commands into an arbitrary image, e.g. for render-to-texture or post-processing.
+    ///
+    /// The image is transitioned from `target.initial_layout` to `target.final_layout`.
+    /// In render pass mode, a framebuffer is created for `target.image_view` and cached until
+    /// [`Self::release_target`] is called.
+    /// If egui requested a screenshot, `target.image` is copied as in [`Self::paint`] and must then have been
+    /// created with `TRANSFER_SRC` usage.
+    pub fn paint_to_target(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        target: &PaintTarget,
+        clipped_meshes: Vec<egui::ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<Option<TimelineSignal>, IntegrationError> {
+        self.check_not_destroyed()?;
+        let render_pass = match self.rendering_mode {
+            RenderingMode::RenderPass => self.cached_render_pass(
+                target.format,
+                target.load.load_op(),
+                target.initial_layout,
+                target.final_layout,
+            )?,
+            RenderingMode::DynamicRendering => vk::RenderPass::null(),
+        };
+        let pipeline = self.target_pipeline(render_pass, target.format)?;
+
+        let attachment = match self.rendering_mode {
+            RenderingMode::RenderPass => {
+                let up_to_date = self
+                    .target_framebuffers
+                    .get(&target.image_view)
+                    .is_some_and(|cached| {
+                        cached.extent == target.extent && cached.format == target.format
+                    });
+                if !up_to_date {
+                    self.release_target(target.image_view);
+                    let framebuffer = self.create_framebuffer(
+                        render_pass,
+                        target.image_view,
+                        target.format,
+                        target.extent,
+                    )?;
+                    self.target_framebuffers.insert(
+                        target.image_view,
+                        TargetFramebuffer {
+                            framebuffer,
+                            extent: target.extent,
+                            format: target.format,
+                        },
+                    );
+                }
+                TargetAttachment::Framebuffer {
+                    render_pass,
+                    framebuffer: self.target_framebuffers[&target.image_view].framebuffer,
+                }
+            }
+            RenderingMode::DynamicRendering => TargetAttachment::ImageView {
+                image_view: target.image_view,
+                msaa: self.msaa_attachment(target.format, target.extent)?,
+                transition: Some((target.image, target.initial_layout, target.final_layout)),
+            },
+        };
+        let render_target = RenderTarget {
+            extent: target.extent,
+            format: target.format,
+            pixels_per_point: self.scale_factor as f32,
+            pipeline,
+            load: target.load,
+            attachment,
+        };
+        let signal = self.paint_target(
+            command_buffer,
+            None,
+            render_target,
+            clipped_meshes,
+            textures_delta,
+        )?;
+        self.take_screenshot(
+            command_buffer,
+            target.image,
+            target.final_layout,
+            target.format,
+            target.extent,
+        )?;
+        Ok(signal)
+    }
+
+    // Get the render pass for an attachment, creating it on first use.
+    fn cached_render_pass(
+        &mut self,
+        format: vk::Format,
+        load_op: vk::AttachmentLoadOp,
+        initial_layout: vk::ImageLayout,
+        final_layout: vk::ImageLayout,
+    ) -> Result<vk::RenderPass, IntegrationError> {
+        let key = (
+            format,
+            self.sample_count,
+            load_op,
+            initial_layout,
+            final_layout,
+        );
+        if let Some(&render_pass) = self.render_passes.get(&key) {
+            return Ok(render_pass);
+        }
+        let render_pass = create_render_pass(
+            &self.device,
+            format,
+            self.sample_count,
+            load_op,
+            initial_layout,
+            final_layout,
+        )?;
+        self.render_passes.insert(key, render_pass);
+        Ok(render_pass)
+    }
+
+    // Describe drawing to a swapchain image as configured by the target description.
+    fn swapchain_target(
+        &mut self,
+        swapchain_image_index: usize,
+    ) -> Result<RenderTarget, IntegrationError> {
+        if swapchain_image_index >= self.swapchain_images.len() {
+            return Err(IntegrationError::Misuse(
+                "swapchain_image_index is out of range",
+            ));
+        }
+        let attachment = match self.rendering_mode {
+            RenderingMode::RenderPass => TargetAttachment::Framebuffer {
+                render_pass: self.render_pass,
+                framebuffer: self.framebuffers[swapchain_image_index],
+            },
+            RenderingMode::DynamicRendering => TargetAttachment::ImageView {
+                image_view: self.framebuffer_color_image_views[swapchain_image_index],
+                msaa: self.msaa_attachment(self.swapchain_format, self.swapchain_extent())?,
+                transition: Some((
+                    self.swapchain_images[swapchain_image_index],
+                    self.target_desc.initial_layout,
+                    self.target_desc.final_layout,
+                )),
+            },
+        };
+        Ok(RenderTarget {
+            extent: self.swapchain_extent(),
+            format: self.swapchain_format,
+            pixels_per_point: self.scale_factor as f32,
+            pipeline: self.pipeline,
+            load: self.target_desc.load,
+            attachment,
+        })
+    }
+
+    fn swapchain_extent(&self) -> vk::Extent2D {
+        vk::Extent2D::default()
+            .width(self.physical_width)
+            .height(self.physical_height)
+    }
+
+    // Get the multisampled attachment for a target if multisampling is enabled, creating it on first use.
+    fn msaa_attachment(
+        &mut self,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Result<Option<(vk::Image, vk::ImageView)>, IntegrationError> {
+        if self.sample_count == vk::SampleCountFlags::TYPE_1 {
+            return Ok(None);
+        }
+        if !self.msaa_images.contains_key(&(format, extent)) {
+            let msaa_image = create_attachment_image(
+                &self.device,
+                &self.allocator,
+                format,
+                extent,
+                self.sample_count,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            )?;
+            self.msaa_images.insert((format, extent), msaa_image);
+        }
+        let msaa_image = &self.msaa_images[&(format, extent)];
+        Ok(Some((msaa_image.image, msaa_image.image_view)))
+    }
+
+    // Create a framebuffer for `image_view`, preceded by the multisampled attachment if multisampling is enabled.
+    fn create_framebuffer(
+        &mut self,
+        render_pass: vk::RenderPass,
+        image_view: vk::ImageView,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Result<vk::Framebuffer, IntegrationError> {
+        let mut attachments = vec![];
+        if let Some((_, msaa_image_view)) = self.msaa_attachment(format, extent)? {
+            attachments.push(msaa_image_view);
+        }
+        attachments.push(image_view);
         unsafe {
-            self.device.cmd_begin_render_pass(
-                command_buffer,
-                &vk::RenderPassBeginInfo::default()
-                    .render_pass(self.render_pass)
-                    .framebuffer(self.framebuffers[index])
-                    .clear_values(&[])
-                    .render_area(
-                        vk::Rect2D::default().extent(
-                            vk::Extent2D::default()
-                                .width(self.physical_width)
-                                .height(self.physical_height),
+            self.device.create_framebuffer(
+                &vk::FramebufferCreateInfo::default()
+                    .render_pass(render_pass)
+                    .attachments(&attachments)
+                    .width(extent.width)
+                    .height(extent.height)
+                    .layers(1),
+                None,
+            )
+        }
+        .map_err(IntegrationError::from)
+    }
+
+    // Create the framebuffers of the swapchain images in render pass mode.
+    fn create_swapchain_framebuffers(&mut self) -> Result<Vec<vk::Framebuffer>, IntegrationError> {
+        self.create_framebuffers(
+            &self.framebuffer_color_image_views.clone(),
+            self.swapchain_format,
+            self.swapchain_extent(),
+        )
+    }
+
+    // Create framebuffers for the images of a swapchain in render pass mode.
+    fn create_framebuffers(
+        &mut self,
+        image_views: &[vk::ImageView],
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Result<Vec<vk::Framebuffer>, IntegrationError> {
+        match self.rendering_mode {
+            RenderingMode::RenderPass => {
+                let render_pass = self.cached_render_pass(
+                    format,
+                    self.target_desc.load.load_op(),
+                    self.target_desc.initial_layout,
+                    self.target_desc.final_layout,
+                )?;
+                let mut framebuffers = Vec::with_capacity(image_views.len());
+                for &image_view in image_views {
+                    match self.create_framebuffer(render_pass, image_view, format, extent) {
+                        Ok(framebuffer) => framebuffers.push(framebuffer),
+                        Err(err) => {
+                            for framebuffer in framebuffers {
+                                unsafe { self.device.destroy_framebuffer(framebuffer, None) };
+                            }
+                            return Err(err);
+                        }
+                    }
+                }
+                Ok(framebuffers)
+            }
+            RenderingMode::DynamicRendering => Ok(vec![]),
+        }
+    }
+
+    // Get the pipeline for a format other than the swapchain format, creating it on first use.
+    fn target_pipeline(
+        &mut self,
+        render_pass: vk::RenderPass,
+        format: vk::Format,
+    ) -> Result<vk::Pipeline, IntegrationError> {
+        // Pipelines only depend on the format, so any render pass of the format is compatible
+        if let Some(&pipeline) = self.target_pipelines.get(&format) {
+            return Ok(pipeline);
+        }
+        let pipeline = create_pipeline(
+            &self.device,
+            self.pipeline_layout,
+            render_pass,
+            format,
+            self.sample_count,
+        )?;
+        self.target_pipelines.insert(format, pipeline);
+        Ok(pipeline)
+    }
+
+    /// Destroy the framebuffer cached for a [`PaintTarget`]'s image view.
+    ///
+    /// Call this before destroying an image view passed to [`Self::paint_to_target`],
+    /// once the GPU has finished using it.
+    pub fn release_target(&mut self, image_view: vk::ImageView) {
+        if let Some(cached) = self.target_framebuffers.remove(&image_view) {
+            unsafe {
+                self.device.destroy_framebuffer(cached.framebuffer, None);
+            }
+        }
+    }
+
+    // Record uploads into `command_buffer`, and draws into `secondary_command_buffer` if given or
+    // else into `command_buffer` between beginning and ending rendering.
+    fn paint_target(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        secondary_command_buffer: Option<vk::CommandBuffer>,
+        target: RenderTarget,
+        clipped_meshes: Vec<egui::ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<Option<TimelineSignal>, IntegrationError> {
+        // The first paint of a frame reuses the buffers of the frame that used this slot before,
+        // which the GPU has finished with
+        let frame = &mut self.frame_buffers[self.frame_index];
+        let new_frame = frame.frame != self.frame_counter;
+        if new_frame {
+            if let Some((semaphore, _)) = &self.timeline {
+                if frame.timeline_value != 0 {
+                    unsafe {
+                        self.device.wait_semaphores(
+                            &vk::SemaphoreWaitInfo::default()
+                                .semaphores(std::slice::from_ref(semaphore))
+                                .values(&[frame.timeline_value]),
+                            u64::MAX,
+                        )?;
+                    }
+                    frame.timeline_value = 0;
+                }
+            }
+            frame.frame = self.frame_counter;
+            frame.vertex_offset = 0;
+            frame.index_offset = 0;
+            frame.staging_offset = 0;
+            frame.destroy_retired(&self.device, &self.allocator, &mut self.descriptor_pools)?;
+            if let Some(transfer_queue) = &mut self.transfer_queue {
+                transfer_queue.reset(&self.device, self.frame_index)?;
+            }
+        }
+
+        let shrink = new_frame && self.shrink_buffers;
+        let result = self.record_target(
+            command_buffer,
+            secondary_command_buffer,
+            target,
+            clipped_meshes,
+            textures_delta.set,
+            shrink,
+        );
+
+        // The draws recorded above may still use freed textures, which are retired even if painting
+        // failed so that they aren't leaked
+        for &id in &textures_delta.free {
+            self.retire_texture(id);
+        }
+        // Likewise unregister user textures whose handles were dropped only after the draws, which may use
+        // them, and free unregistered user textures with this frame since earlier frames may still use them
+        self.user_textures.unregister_dropped();
+        let unregistered = self.user_textures.take_unregistered();
+        self.frame_buffers[self.frame_index]
+            .retired_descriptor_sets
+            .extend(unregistered);
+        result?;
+
+        // Hand out the frame's value only once painting succeeded, so that every value waited for is signalled
+        let frame = &mut self.frame_buffers[self.frame_index];
+        Ok(self.timeline.as_mut().map(|(semaphore, value)| {
+            if frame.timeline_value == 0 {
+                *value += 1;
+                frame.timeline_value = *value;
+            }
+            TimelineSignal {
+                semaphore: *semaphore,
+                value: frame.timeline_value,
+            }
+        }))
+    }
+
+    // Record the texture uploads and draws of one paint call into the current frame's buffers.
+    fn record_target(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        secondary_command_buffer: Option<vk::CommandBuffer>,
+        target: RenderTarget,
+        clipped_meshes: Vec<egui::ClippedPrimitive>,
+        texture_deltas: Vec<(TextureId, ImageDelta)>,
+        shrink: bool,
+    ) -> Result<(), IntegrationError> {
+        // Check everything before recording, so that an error leaves neither textures half uploaded
+        // nor rendering begun
+        if self.sample_count != vk::SampleCountFlags::TYPE_1 && target.load == AttachmentLoad::Load
+        {
+            return Err(IntegrationError::Misuse(
+                "AttachmentLoad::Load is not supported with multisampling",
+            ));
+        }
+        for clipped_primitive in &clipped_meshes {
+            match &clipped_primitive.primitive {
+                // Managed textures may also be created by this paint's deltas
+                egui::epaint::Primitive::Mesh(mesh)
+                    if matches!(mesh.texture_id, TextureId::Managed(_))
+                        && !self.texture_desc_sets.contains_key(&mesh.texture_id)
+                        && !texture_deltas
+                            .iter()
+                            .any(|(id, delta)| *id == mesh.texture_id && delta.pos.is_none()) =>
+                {
+                    return Err(IntegrationError::Misuse(
+                        "A mesh uses a managed texture that egui has not set",
+                    ));
+                }
+                egui::epaint::Primitive::Mesh(_) => {}
+                egui::epaint::Primitive::Callback(callback) => {
+                    if !callback.callback.is::<Callback>() {
+                        return Err(IntegrationError::Misuse(
+                            "Unsupported paint callback; use egui_winit_ash_integration::Callback",
+                        ));
+                    }
+                }
+            }
+        }
+
+        // upload textures before the render pass
+        self.update_textures(command_buffer, texture_deltas, shrink)?;
+
+        let frame = &mut self.frame_buffers[self.frame_index];
+
+        // Grow buffers to fit this frame
+        let (vertex_size, index_size) =
+            clipped_meshes
+                .iter()
+                .fold((0, 0), |(vertex_size, index_size), clipped_primitive| {
+                    match &clipped_primitive.primitive {
+                        egui::epaint::Primitive::Mesh(mesh) => (
+                            vertex_size + std::mem::size_of_val(mesh.vertices.as_slice()) as u64,
+                            index_size + std::mem::size_of_val(mesh.indices.as_slice()) as u64,
                         ),
-                    ),
-                vk::SubpassContents::INLINE,
-            );
+                        egui::epaint::Primitive::Callback(_) => (vertex_size, index_size),
+                    }
+                });
+        // Earlier paint calls of this frame still use the start of the buffers
+        if let Some(retired) = frame.vertex_buffer.reserve(
+            &self.device,
+            &self.allocator,
+            frame.vertex_offset + vertex_size,
+            shrink,
+        )? {
+            frame.retired_buffers.push(retired);
+            frame.vertex_offset = 0;
+        }
+        if let Some(retired) = frame.index_buffer.reserve(
+            &self.device,
+            &self.allocator,
+            frame.index_offset + index_size,
+            shrink,
+        )? {
+            frame.retired_buffers.push(retired);
+            frame.index_offset = 0;
         }
+        let mut vertex_buffer_ptr = unsafe {
+            frame
+                .vertex_buffer
+                .mapped_ptr()?
+                .add(frame.vertex_offset as usize)
+        };
+        let mut index_buffer_ptr = unsafe {
+            frame
+                .index_buffer
+                .mapped_ptr()?
+                .add(frame.index_offset as usize)
+        };
+        let mut vertex_base =
+            (frame.vertex_offset / std::mem::size_of::<egui::epaint::Vertex>() as u64) as i32;
+        let mut index_base = (frame.index_offset / std::mem::size_of::<u32>() as u64) as u32;
+        frame.vertex_offset += vertex_size;
+        frame.index_offset += index_size;
+
+        // prepare callback resources
+        self.callback_resources
+            .prepare(&self.device, command_buffer);
+
+        // begin render pass
+        let command_buffer = match secondary_command_buffer {
+            Some(secondary_command_buffer) => {
+                self.begin_secondary(secondary_command_buffer, &target)?;
+                secondary_command_buffer
+            }
+            None => {
+                self.begin_rendering(command_buffer, &target, false);
+                command_buffer
+            }
+        };
 
         // bind resources
-        unsafe {
-            self.device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline,
-            );
-            self.device.cmd_bind_vertex_buffers(
-                command_buffer,
-                0,
-                &[self.vertex_buffers[index]],
-                &[0],
-            );
-            self.device.cmd_bind_index_buffer(
-                command_buffer,
-                self.index_buffers[index],
-                0,
-                vk::IndexType::UINT32,
-            );
-            self.device.cmd_set_viewport(
-                command_buffer,
-                0,
-                &[vk::Viewport::default()
-                    .x(0.0)
-                    .y(0.0)
-                    .width(self.physical_width as f32)
-                    .height(self.physical_height as f32)
-                    .min_depth(0.0)
-                    .max_depth(1.0)],
-            );
-            let width_points = self.physical_width as f32 / self.scale_factor as f32;
-            let height_points = self.physical_height as f32 / self.scale_factor as f32;
-            self.device.cmd_push_constants(
-                command_buffer,
-                self.pipeline_layout,
-                vk::ShaderStageFlags::VERTEX,
-                0,
-                bytes_of(&width_points),
-            );
-            self.device.cmd_push_constants(
-                command_buffer,
-                self.pipeline_layout,
-                vk::ShaderStageFlags::VERTEX,
-                std::mem::size_of_val(&width_points) as u32,
-                bytes_of(&height_points),
-            );
-        }
+        self.bind_resources(command_buffer, &target);
 
         // render meshes
-        let mut vertex_base = 0;
-        let mut index_base = 0;
         for egui::ClippedPrimitive {
             clip_rect,
             primitive,
@@ -656,40 +2072,28 @@ impl<A: AllocatorTrait> Integration<A> {
         {
             let mesh = match primitive {
                 egui::epaint::Primitive::Mesh(mesh) => mesh,
-                egui::epaint::Primitive::Callback(_) => todo!(),
+                egui::epaint::Primitive::Callback(callback) => {
+                    self.paint_callback(command_buffer, &target, clip_rect, callback);
+                    continue;
+                }
             };
             if mesh.vertices.is_empty() || mesh.indices.is_empty() {
                 continue;
             }
 
+            // Meshes of unregistered user textures are skipped
+            let Some(descriptor_set) = self.mesh_descriptor_set(mesh.texture_id) else {
+                continue;
+            };
             unsafe {
-                if let egui::TextureId::User(id) = mesh.texture_id {
-                    if let Some(descriptor_set) = self.user_textures[id as usize] {
-                        self.device.cmd_bind_descriptor_sets(
-                            command_buffer,
-                            vk::PipelineBindPoint::GRAPHICS,
-                            self.pipeline_layout,
-                            0,
-                            &[descriptor_set],
-                            &[],
-                        );
-                    } else {
-                        eprintln!(
-                            "This UserTexture has already been unregistered: {:?}",
-                            mesh.texture_id
-                        );
-                        continue;
-                    }
-                } else {
-                    self.device.cmd_bind_descriptor_sets(
-                        command_buffer,
-                        vk::PipelineBindPoint::GRAPHICS,
-                        self.pipeline_layout,
-                        0,
-                        &[*self.texture_desc_sets.get(&mesh.texture_id).unwrap()],
-                        &[],
-                    );
-                }
+                self.device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline_layout,
+                    0,
+                    &[descriptor_set],
+                    &[],
+                );
             }
             let v_slice = &mesh.vertices;
             let v_size = std::mem::size_of_val(&v_slice[0]);
@@ -702,12 +2106,6 @@ impl<A: AllocatorTrait> Integration<A> {
             let vertex_buffer_ptr_next = unsafe { vertex_buffer_ptr.add(v_copy_size) };
             let index_buffer_ptr_next = unsafe { index_buffer_ptr.add(i_copy_size) };
 
-            if vertex_buffer_ptr_next >= vertex_buffer_ptr_end
-                || index_buffer_ptr_next >= index_buffer_ptr_end
-            {
-                panic!("egui paint out of memory");
-            }
-
             // map memory
             unsafe { vertex_buffer_ptr.copy_from(v_slice.as_ptr() as *const u8, v_copy_size) };
             unsafe { index_buffer_ptr.copy_from(i_slice.as_ptr() as *const u8, i_copy_size) };
@@ -719,21 +2117,21 @@ impl<A: AllocatorTrait> Integration<A> {
             unsafe {
                 let min = clip_rect.min;
                 let min = egui::Pos2 {
-                    x: min.x * self.scale_factor as f32,
-                    y: min.y * self.scale_factor as f32,
+                    x: min.x * target.pixels_per_point,
+                    y: min.y * target.pixels_per_point,
                 };
                 let min = egui::Pos2 {
-                    x: f32::clamp(min.x, 0.0, self.physical_width as f32),
-                    y: f32::clamp(min.y, 0.0, self.physical_height as f32),
+                    x: f32::clamp(min.x, 0.0, target.extent.width as f32),
+                    y: f32::clamp(min.y, 0.0, target.extent.height as f32),
                 };
                 let max = clip_rect.max;
                 let max = egui::Pos2 {
-                    x: max.x * self.scale_factor as f32,
-                    y: max.y * self.scale_factor as f32,
+                    x: max.x * target.pixels_per_point,
+                    y: max.y * target.pixels_per_point,
                 };
                 let max = egui::Pos2 {
-                    x: f32::clamp(max.x, min.x, self.physical_width as f32),
-                    y: f32::clamp(max.y, min.y, self.physical_height as f32),
+                    x: f32::clamp(max.x, min.x, target.extent.width as f32),
+                    y: f32::clamp(max.y, min.y, target.extent.height as f32),
                 };
                 self.device.cmd_set_scissor(
                     command_buffer,
@@ -765,104 +2163,502 @@ impl<A: AllocatorTrait> Integration<A> {
         }
 
         // end render pass
-        unsafe {
-            self.device.cmd_end_render_pass(command_buffer);
+        match secondary_command_buffer {
+            Some(_) => unsafe {
+                self.device.end_command_buffer(command_buffer)?;
+            },
+            None => self.end_rendering(command_buffer, &target),
         }
+        Ok(())
+    }
 
-        for &id in &textures_delta.free {
-            self.texture_desc_sets.remove_entry(&id); // dsc_set is destroyed with dsc_pool
-            self.texture_image_infos.remove_entry(&id);
-            if let Some((_, image)) = self.texture_images.remove_entry(&id) {
-                unsafe {
-                    self.device.destroy_image(image, None);
+    // Begin the render pass or dynamic rendering for `target`, with inline contents unless `secondary` is set.
+    fn begin_rendering(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        target: &RenderTarget,
+        secondary: bool,
+    ) {
+        let render_area = vk::Rect2D::default().extent(target.extent);
+        match target.attachment {
+            TargetAttachment::Framebuffer {
+                render_pass,
+                framebuffer,
+            } => unsafe {
+                self.device.cmd_begin_render_pass(
+                    command_buffer,
+                    &vk::RenderPassBeginInfo::default()
+                        .render_pass(render_pass)
+                        .framebuffer(framebuffer)
+                        .clear_values(&[target.load.clear_value()])
+                        .render_area(render_area),
+                    if secondary {
+                        vk::SubpassContents::SECONDARY_COMMAND_BUFFERS
+                    } else {
+                        vk::SubpassContents::INLINE
+                    },
+                );
+            },
+            TargetAttachment::ImageView {
+                image_view,
+                msaa,
+                transition,
+            } => unsafe {
+                match transition {
+                    Some((image, initial_layout, _))
+                        if initial_layout != vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL =>
+                    {
+                        insert_image_memory_barrier(
+                            &self.device,
+                            &command_buffer,
+                            &image,
+                            vk::QUEUE_FAMILY_IGNORED,
+                            vk::QUEUE_FAMILY_IGNORED,
+                            vk::AccessFlags::MEMORY_WRITE,
+                            vk::AccessFlags::COLOR_ATTACHMENT_READ
+                                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                            initial_layout,
+                            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                            vk::PipelineStageFlags::ALL_COMMANDS,
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                            color_subresource_range(),
+                        );
+                    }
+                    _ => {
+                        // Same dependency as the subpass dependency of the render pass
+                        self.device.cmd_pipeline_barrier(
+                            command_buffer,
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                            vk::DependencyFlags::BY_REGION,
+                            &[vk::MemoryBarrier::default()
+                                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                                .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)],
+                            &[],
+                            &[],
+                        );
+                    }
                 }
+                let color_attachment = match msaa {
+                    Some((msaa_image, msaa_image_view)) => {
+                        // The previous contents of the multisampled image are never needed
+                        insert_image_memory_barrier(
+                            &self.device,
+                            &command_buffer,
+                            &msaa_image,
+                            vk::QUEUE_FAMILY_IGNORED,
+                            vk::QUEUE_FAMILY_IGNORED,
+                            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                            vk::AccessFlags::COLOR_ATTACHMENT_READ
+                                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                            vk::ImageLayout::UNDEFINED,
+                            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                            color_subresource_range(),
+                        );
+                        vk::RenderingAttachmentInfo::default()
+                            .image_view(msaa_image_view)
+                            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                            .resolve_mode(vk::ResolveModeFlags::AVERAGE)
+                            .resolve_image_view(image_view)
+                            .resolve_image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                            .load_op(target.load.load_op())
+                            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                    }
+                    None => vk::RenderingAttachmentInfo::default()
+                        .image_view(image_view)
+                        .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                        .load_op(target.load.load_op())
+                        .store_op(vk::AttachmentStoreOp::STORE),
+                };
+                let color_attachments = [color_attachment.clear_value(target.load.clear_value())];
+                self.device.cmd_begin_rendering(
+                    command_buffer,
+                    &vk::RenderingInfo::default()
+                        .flags(if secondary {
+                            vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS
+                        } else {
+                            vk::RenderingFlags::empty()
+                        })
+                        .render_area(render_area)
+                        .layer_count(1)
+                        .color_attachments(&color_attachments),
+                );
+            },
+        }
+    }
+
+    // Begin a secondary command buffer that continues the render pass or dynamic rendering of `target`.
+    fn begin_secondary(
+        &self,
+        secondary_command_buffer: vk::CommandBuffer,
+        target: &RenderTarget,
+    ) -> Result<(), IntegrationError> {
+        let color_attachment_formats = [self.swapchain_format];
+        let mut rendering_info = vk::CommandBufferInheritanceRenderingInfo::default()
+            .color_attachment_formats(&color_attachment_formats)
+            .rasterization_samples(self.sample_count);
+        let inheritance_info = match target.attachment {
+            TargetAttachment::Framebuffer {
+                render_pass,
+                framebuffer,
+            } => vk::CommandBufferInheritanceInfo::default()
+                .render_pass(render_pass)
+                .subpass(0)
+                .framebuffer(framebuffer),
+            TargetAttachment::ImageView { .. } => {
+                vk::CommandBufferInheritanceInfo::default().push_next(&mut rendering_info)
             }
-            if let Some((_, image_view)) = self.texture_image_views.remove_entry(&id) {
+        };
+        unsafe {
+            self.device.begin_command_buffer(
+                secondary_command_buffer,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(
+                        vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT
+                            | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+                    )
+                    .inheritance_info(&inheritance_info),
+            )?;
+        }
+        Ok(())
+    }
+
+    // End the render pass or dynamic rendering for `target`.
+    fn end_rendering(&self, command_buffer: vk::CommandBuffer, target: &RenderTarget) {
+        match target.attachment {
+            TargetAttachment::Framebuffer { .. } => unsafe {
+                self.device.cmd_end_render_pass(command_buffer);
+            },
+            TargetAttachment::ImageView { transition, .. } => {
                 unsafe {
-                    self.device.destroy_image_view(image_view, None);
+                    self.device.cmd_end_rendering(command_buffer);
+                }
+                match transition {
+                    Some((image, _, vk::ImageLayout::PRESENT_SRC_KHR)) => {
+                        insert_image_memory_barrier(
+                            &self.device,
+                            &command_buffer,
+                            &image,
+                            vk::QUEUE_FAMILY_IGNORED,
+                            vk::QUEUE_FAMILY_IGNORED,
+                            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                            vk::AccessFlags::NONE,
+                            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                            vk::ImageLayout::PRESENT_SRC_KHR,
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                            color_subresource_range(),
+                        );
+                    }
+                    Some((image, _, final_layout))
+                        if final_layout != vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL =>
+                    {
+                        insert_image_memory_barrier(
+                            &self.device,
+                            &command_buffer,
+                            &image,
+                            vk::QUEUE_FAMILY_IGNORED,
+                            vk::QUEUE_FAMILY_IGNORED,
+                            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+                            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                            final_layout,
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                            vk::PipelineStageFlags::ALL_COMMANDS,
+                            color_subresource_range(),
+                        );
+                    }
+                    _ => (),
                 }
             }
-            if let Some((_, allocation)) = self.texture_allocations.remove_entry(&id) {
-                self.allocator.free(allocation).unwrap();
-            }
         }
     }
 
-    fn update_texture(&mut self, texture_id: TextureId, delta: ImageDelta) {
+    // Bind the pipeline, vertex/index buffers, viewport and push constants used by egui meshes.
+    fn bind_resources(&self, command_buffer: vk::CommandBuffer, target: &RenderTarget) {
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                target.pipeline,
+            );
+            self.device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[self.frame_buffers[self.frame_index].vertex_buffer.buffer()],
+                &[0],
+            );
+            self.device.cmd_bind_index_buffer(
+                command_buffer,
+                self.frame_buffers[self.frame_index].index_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+            self.device.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[vk::Viewport::default()
+                    .x(0.0)
+                    .y(0.0)
+                    .width(target.extent.width as f32)
+                    .height(target.extent.height as f32)
+                    .min_depth(0.0)
+                    .max_depth(1.0)],
+            );
+            let width_points = target.extent.width as f32 / target.pixels_per_point;
+            let height_points = target.extent.height as f32 / target.pixels_per_point;
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                bytes_of(&width_points),
+            );
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                std::mem::size_of_val(&width_points) as u32,
+                bytes_of(&height_points),
+            );
+        }
+    }
+
+    // Record a paint callback and restore the state egui meshes depend on.
+    fn paint_callback(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        target: &RenderTarget,
+        clip_rect: egui::Rect,
+        callback: egui::PaintCallback,
+    ) {
+        // Other callbacks were rejected before recording
+        let Some(callback_fn) = callback.callback.downcast_ref::<Callback>() else {
+            return;
+        };
+
+        let info = egui::PaintCallbackInfo {
+            viewport: callback.rect,
+            clip_rect,
+            pixels_per_point: target.pixels_per_point,
+            screen_size_px: [target.extent.width, target.extent.height],
+        };
+        let viewport_px = callback.rect * info.pixels_per_point;
+        if viewport_px.width() < 1.0 || viewport_px.height() < 1.0 {
+            return;
+        }
+        let clip_px = info.clip_rect_in_pixels();
+        if clip_px.width_px <= 0 || clip_px.height_px <= 0 {
+            return;
+        }
+
+        let viewport = vk::Viewport::default()
+            .x(viewport_px.min.x)
+            .y(viewport_px.min.y)
+            .width(viewport_px.width())
+            .height(viewport_px.height())
+            .min_depth(0.0)
+            .max_depth(1.0);
+        let scissor = vk::Rect2D::default()
+            .offset(vk::Offset2D::default().x(clip_px.left_px).y(clip_px.top_px))
+            .extent(
+                vk::Extent2D::default()
+                    .width(clip_px.width_px as u32)
+                    .height(clip_px.height_px as u32),
+            );
+        unsafe {
+            self.device
+                .cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+            self.device
+                .cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&scissor));
+        }
+
+        callback_fn.paint(
+            info,
+            &CallbackContext {
+                device: &self.device,
+                command_buffer,
+                viewport,
+                scissor,
+                render_pass: target.attachment.render_pass(),
+                format: target.format,
+                sample_count: self.sample_count,
+                resources: &self.callback_resources,
+            },
+        );
+
+        // restore the state changed by the callback
+        self.bind_resources(command_buffer, target);
+    }
+
+    // Get the sampler for egui texture options, creating it on first use.
+    fn sampler(&mut self, options: egui::TextureOptions) -> Result<vk::Sampler, IntegrationError> {
+        if let Some(&sampler) = self.samplers.get(&options) {
+            return Ok(sampler);
+        }
+        let sampler = create_sampler(&self.device, options)?;
+        self.samplers.insert(options, sampler);
+        Ok(sampler)
+    }
+
+    // Record uploads of all texture deltas into `command_buffer` through the staging buffer of the current frame.
+    //
+    // Must be called outside of a render pass. Replaced textures are destroyed once the GPU has
+    // finished the current frame.
+    fn update_textures(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        deltas: Vec<(TextureId, ImageDelta)>,
+        shrink: bool,
+    ) -> Result<(), IntegrationError> {
         // Extract pixel data from egui
-        let data: Vec<u8> = match &delta.image {
-            egui::ImageData::Color(image) => {
-                assert_eq!(
-                    image.width() * image.height(),
-                    image.pixels.len(),
-                    "Mismatch between texture size and texel count"
-                );
-                image
-                    .pixels
-                    .iter()
+        let data = deltas
+            .iter()
+            .map(|(_, delta)| match &delta.image {
+                egui::ImageData::Color(image) => {
+                    assert_eq!(
+                        image.width() * image.height(),
+                        image.pixels.len(),
+                        "Mismatch between texture size and texel count"
+                    );
+                    image
+                        .pixels
+                        .iter()
+                        .flat_map(|color| color.to_array())
+                        .collect::<Vec<u8>>()
+                }
+                egui::ImageData::Font(image) => image
+                    .srgba_pixels(None)
                     .flat_map(|color| color.to_array())
-                    .collect()
-            }
-            egui::ImageData::Font(image) => image
-                .srgba_pixels(None)
-                .flat_map(|color| color.to_array())
-                .collect(),
-        };
-        let cmd_pool = {
-            let cmd_pool_info = vk::CommandPoolCreateInfo::default().queue_family_index(self.qfi);
-            unsafe {
-                self.device
-                    .create_command_pool(&cmd_pool_info, None)
-                    .unwrap()
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+
+        // Copy all deltas after the staged data of earlier paint calls of this frame
+        let size = data.iter().map(|data| data.len() as u64).sum::<u64>();
+        let frame = &mut self.frame_buffers[self.frame_index];
+        if let Some(retired) = frame.staging_buffer.reserve(
+            &self.device,
+            &self.allocator,
+            frame.staging_offset + size,
+            shrink,
+        )? {
+            frame.retired_buffers.push(retired);
+            frame.staging_offset = 0;
+        }
+        let staging_buffer = frame.staging_buffer.buffer();
+        let ptr = frame.staging_buffer.mapped_ptr()?;
+        let mut offset = frame.staging_offset;
+        frame.staging_offset += size;
+
+        // New textures are uploaded on the transfer queue if there is one
+        let transfer_command_buffer = match &mut self.transfer_queue {
+            Some(transfer_queue) if deltas.iter().any(|(_, delta)| delta.pos.is_none()) => {
+                Some(transfer_queue.begin(&self.device, self.frame_index)?)
             }
+            _ => None,
         };
-        let cmd_buff = {
-            let cmd_buff_alloc_info = vk::CommandBufferAllocateInfo::default()
-                .command_buffer_count(1u32)
-                .command_pool(cmd_pool)
-                .level(vk::CommandBufferLevel::PRIMARY);
-            unsafe {
-                self.device
-                    .allocate_command_buffers(&cmd_buff_alloc_info)
-                    .unwrap()[0]
+        let result = deltas
+            .into_iter()
+            .zip(data)
+            .try_for_each(|((texture_id, delta), data)| {
+                unsafe {
+                    ptr.add(offset as usize)
+                        .copy_from_nonoverlapping(data.as_ptr(), data.len());
+                }
+                self.update_texture(
+                    command_buffer,
+                    transfer_command_buffer,
+                    (staging_buffer, offset),
+                    texture_id,
+                    delta,
+                )?;
+                offset += data.len() as u64;
+                Ok(())
+            });
+        match (&mut self.transfer_queue, transfer_command_buffer) {
+            (Some(transfer_queue), Some(_)) => {
+                let result =
+                    result.and_then(|()| transfer_queue.submit(&self.device, self.frame_index));
+                if result.is_err() {
+                    transfer_queue.cancel(&self.device, self.frame_index).ok();
+                }
+                result
             }
+            _ => result,
+        }
+    }
+
+    // Record the copy of one delta from a staging buffer and offset into its texture.
+    //
+    // New textures are copied in `transfer_command_buffer` if given and then handed over to the graphics queue family.
+    fn update_texture(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        transfer_command_buffer: Option<vk::CommandBuffer>,
+        (staging_buffer, buffer_offset): (vk::Buffer, vk::DeviceSize),
+        texture_id: TextureId,
+        delta: ImageDelta,
+    ) -> Result<(), IntegrationError> {
+        let extent = vk::Extent3D {
+            width: delta.image.width() as u32,
+            height: delta.image.height() as u32,
+            depth: 1,
         };
-        let fence_info = vk::FenceCreateInfo::default();
-        let cmd_buff_fence = unsafe { self.device.create_fence(&fence_info, None).unwrap() };
-
-        let (staging_buffer, staging_allocation) = {
-            let buffer_size = data.len() as vk::DeviceSize;
-            let buffer_info = vk::BufferCreateInfo::default()
-                .size(buffer_size)
-                .usage(vk::BufferUsageFlags::TRANSFER_SRC);
-            let texture_buffer = unsafe { self.device.create_buffer(&buffer_info, None) }.unwrap();
-            let requirements =
-                unsafe { self.device.get_buffer_memory_requirements(texture_buffer) };
-            let allocation = self
-                .allocator
-                .allocate(A::AllocationCreateInfo::new(
-                    requirements,
-                    MemoryLocation::CpuToGpu,
-                    true,
-                ))
-                .unwrap();
-            unsafe {
-                self.device
-                    .bind_buffer_memory(texture_buffer, allocation.memory(), allocation.offset())
-                    .unwrap()
+
+        if let Some(pos) = delta.pos {
+            // Copy into the region of the existing texture (e.g. font changed)
+            let Some(&texture_image) = self.texture_images.get(&texture_id) else {
+                return Ok(());
             };
-            (texture_buffer, allocation)
-        };
-        let ptr = staging_allocation.mapped_ptr().unwrap().as_ptr() as *mut u8;
-        unsafe {
-            ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
+            // Keep the contents outside of the region
+            insert_image_memory_barrier(
+                &self.device,
+                &command_buffer,
+                &texture_image,
+                vk::QUEUE_FAMILY_IGNORED,
+                vk::QUEUE_FAMILY_IGNORED,
+                vk::AccessFlags::SHADER_READ,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::TRANSFER,
+                color_subresource_range(),
+            );
+            self.copy_to_texture(
+                command_buffer,
+                staging_buffer,
+                buffer_offset,
+                texture_image,
+                vk::Offset3D {
+                    x: pos[0] as i32,
+                    y: pos[1] as i32,
+                    z: 0,
+                },
+                extent,
+            );
+            insert_image_memory_barrier(
+                &self.device,
+                &command_buffer,
+                &texture_image,
+                vk::QUEUE_FAMILY_IGNORED,
+                vk::QUEUE_FAMILY_IGNORED,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                color_subresource_range(),
+            );
+            return Ok(());
         }
-        let (texture_image, info, texture_allocation) = {
-            let extent = vk::Extent3D {
-                width: delta.image.width() as u32,
-                height: delta.image.height() as u32,
-                depth: 1,
-            };
+
+        // Otherwise create a new texture
+        let (texture_image, texture_allocation) = {
             let create_info = vk::ImageCreateInfo::default()
                 .array_layers(1)
                 .extent(extent)
@@ -874,316 +2670,195 @@ impl<A: AllocatorTrait> Integration<A> {
                 .samples(vk::SampleCountFlags::TYPE_1)
                 .sharing_mode(vk::SharingMode::EXCLUSIVE)
                 .tiling(vk::ImageTiling::OPTIMAL)
-                .usage(
-                    vk::ImageUsageFlags::SAMPLED
-                        | vk::ImageUsageFlags::TRANSFER_DST
-                        | vk::ImageUsageFlags::TRANSFER_SRC,
-                );
-            let handle = unsafe { self.device.create_image(&create_info, None) }.unwrap();
+                .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST);
+            let handle = unsafe { self.device.create_image(&create_info, None) }?;
             let requirements = unsafe { self.device.get_image_memory_requirements(handle) };
-            let allocation = self
-                .allocator
-                .allocate(A::AllocationCreateInfo::new(
-                    requirements,
-                    MemoryLocation::GpuOnly,
-                    false,
-                ))
-                .unwrap();
+            let allocation = self.allocator.allocate(A::AllocationCreateInfo::new(
+                requirements,
+                MemoryLocation::GpuOnly,
+                false,
+            ))?;
             unsafe {
                 self.device
-                    .bind_image_memory(handle, allocation.memory(), allocation.offset())
-                    .unwrap()
+                    .bind_image_memory(handle, allocation.memory(), allocation.offset())?
             };
-            (handle, create_info, allocation)
+            (handle, allocation)
         };
-        self.texture_image_infos.insert(texture_id, info);
         let texture_image_view = {
             let create_info = vk::ImageViewCreateInfo::default()
                 .components(vk::ComponentMapping::default())
                 .flags(vk::ImageViewCreateFlags::empty())
                 .format(vk::Format::R8G8B8A8_SRGB)
                 .image(texture_image)
-                .subresource_range(
-                    vk::ImageSubresourceRange::default()
-                        .aspect_mask(vk::ImageAspectFlags::COLOR)
-                        .base_array_layer(0)
-                        .base_mip_level(0)
-                        .layer_count(1)
-                        .level_count(1),
-                )
+                .subresource_range(color_subresource_range())
                 .view_type(vk::ImageViewType::TYPE_2D);
-            unsafe { self.device.create_image_view(&create_info, None).unwrap() }
+            unsafe { self.device.create_image_view(&create_info, None)? }
         };
-        // begin cmd buff
-        unsafe {
-            let cmd_buff_begin_info = vk::CommandBufferBeginInfo::default()
-                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-            self.device
-                .begin_command_buffer(cmd_buff, &cmd_buff_begin_info)
-                .unwrap();
-        }
         // Transition texture image for transfer dst
+        let upload_command_buffer = transfer_command_buffer.unwrap_or(command_buffer);
         insert_image_memory_barrier(
             &self.device,
-            &cmd_buff,
+            &upload_command_buffer,
             &texture_image,
             vk::QUEUE_FAMILY_IGNORED,
             vk::QUEUE_FAMILY_IGNORED,
-            vk::AccessFlags::NONE_KHR,
+            vk::AccessFlags::NONE,
             vk::AccessFlags::TRANSFER_WRITE,
             vk::ImageLayout::UNDEFINED,
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            vk::PipelineStageFlags::HOST,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
             vk::PipelineStageFlags::TRANSFER,
-            vk::ImageSubresourceRange::default()
-                .aspect_mask(vk::ImageAspectFlags::COLOR)
-                .base_array_layer(0u32)
-                .layer_count(1u32)
-                .base_mip_level(0u32)
-                .level_count(1u32),
+            color_subresource_range(),
         );
-        let region = vk::BufferImageCopy::default()
-            .buffer_offset(0)
-            .buffer_row_length(delta.image.width() as u32)
-            .buffer_image_height(delta.image.height() as u32)
-            .image_subresource(
-                vk::ImageSubresourceLayers::default()
-                    .aspect_mask(vk::ImageAspectFlags::COLOR)
-                    .base_array_layer(0)
-                    .layer_count(1)
-                    .mip_level(0),
-            )
-            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
-            .image_extent(vk::Extent3D {
-                width: delta.image.width() as u32,
-                height: delta.image.height() as u32,
-                depth: 1,
-            });
-        unsafe {
-            self.device.cmd_copy_buffer_to_image(
-                cmd_buff,
-                staging_buffer,
-                texture_image,
+        self.copy_to_texture(
+            upload_command_buffer,
+            staging_buffer,
+            buffer_offset,
+            texture_image,
+            vk::Offset3D::default(),
+            extent,
+        );
+        match (&self.transfer_queue, transfer_command_buffer) {
+            (Some(transfer_queue), Some(transfer_command_buffer)) => {
+                // Release the texture from the transfer queue family...
+                insert_image_memory_barrier(
+                    &self.device,
+                    &transfer_command_buffer,
+                    &texture_image,
+                    transfer_queue.queue_family_index,
+                    self.qfi,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::NONE,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    color_subresource_range(),
+                );
+                // ...and acquire it on the graphics queue family after the upload semaphore wait
+                insert_image_memory_barrier(
+                    &self.device,
+                    &command_buffer,
+                    &texture_image,
+                    transfer_queue.queue_family_index,
+                    self.qfi,
+                    vk::AccessFlags::NONE,
+                    vk::AccessFlags::SHADER_READ,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    color_subresource_range(),
+                );
+            }
+            _ => insert_image_memory_barrier(
+                &self.device,
+                &command_buffer,
+                &texture_image,
+                vk::QUEUE_FAMILY_IGNORED,
+                vk::QUEUE_FAMILY_IGNORED,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &[region],
-            );
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                color_subresource_range(),
+            ),
         }
-        insert_image_memory_barrier(
-            &self.device,
-            &cmd_buff,
-            &texture_image,
-            vk::QUEUE_FAMILY_IGNORED,
-            vk::QUEUE_FAMILY_IGNORED,
-            vk::AccessFlags::TRANSFER_WRITE,
-            vk::AccessFlags::SHADER_READ,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            vk::PipelineStageFlags::TRANSFER,
-            vk::PipelineStageFlags::VERTEX_SHADER,
-            vk::ImageSubresourceRange::default()
-                .aspect_mask(vk::ImageAspectFlags::COLOR)
-                .base_array_layer(0u32)
-                .layer_count(1u32)
-                .base_mip_level(0u32)
-                .level_count(1u32),
-        );
 
+        // update dsc set
+        let sampler = self.sampler(delta.options)?;
+        let dsc_set = self
+            .descriptor_pools
+            .allocate(&self.device, self.descriptor_set_layouts[0])?;
+        let image_info = vk::DescriptorImageInfo::default()
+            .image_view(texture_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(sampler);
+        let dsc_writes = [vk::WriteDescriptorSet::default()
+            .dst_set(dsc_set.set)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .dst_array_element(0_u32)
+            .dst_binding(0_u32)
+            .image_info(std::slice::from_ref(&image_info))];
         unsafe {
-            self.device.end_command_buffer(cmd_buff).unwrap();
-        }
-        let cmd_buffs = [cmd_buff];
-        let submit_infos = [vk::SubmitInfo::default().command_buffers(&cmd_buffs)];
-        unsafe {
-            self.device
-                .queue_submit(self.queue, &submit_infos, cmd_buff_fence)
-                .unwrap();
-            self.device
-                .wait_for_fences(&[cmd_buff_fence], true, u64::MAX)
-                .unwrap();
+            self.device.update_descriptor_sets(&dsc_writes, &[]);
         }
 
-        // texture is now in GPU memory, now we need to decide whether we should register it as new or update existing
+        // register new texture, retiring the one it replaces (e.g. the font atlas grew)
+        self.retire_texture(texture_id);
+        self.texture_images.insert(texture_id, texture_image);
+        self.texture_allocations
+            .insert(texture_id, texture_allocation);
+        self.texture_image_views
+            .insert(texture_id, texture_image_view);
+        self.texture_desc_sets.insert(texture_id, dsc_set);
+        Ok(())
+    }
 
-        if let Some(pos) = delta.pos {
-            // Blit texture data to existing texture if delta pos exists (e.g. font changed)
-            let existing_texture = self.texture_images.get(&texture_id);
-            if let Some(existing_texture) = existing_texture {
-                let info = self.texture_image_infos.get(&texture_id).unwrap();
-                unsafe {
-                    self.device
-                        .reset_command_pool(cmd_pool, vk::CommandPoolResetFlags::empty())
-                        .unwrap();
-                    self.device.reset_fences(&[cmd_buff_fence]).unwrap();
-                    // begin cmd buff
-                    let cmd_buff_begin_info = vk::CommandBufferBeginInfo::default()
-                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-                    self.device
-                        .begin_command_buffer(cmd_buff, &cmd_buff_begin_info)
-                        .unwrap();
-
-                    // Transition existing image for transfer dst
-                    insert_image_memory_barrier(
-                        &self.device,
-                        &cmd_buff,
-                        &existing_texture,
-                        vk::QUEUE_FAMILY_IGNORED,
-                        vk::QUEUE_FAMILY_IGNORED,
-                        vk::AccessFlags::SHADER_READ,
-                        vk::AccessFlags::TRANSFER_WRITE,
-                        vk::ImageLayout::UNDEFINED,
-                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        vk::PipelineStageFlags::FRAGMENT_SHADER,
-                        vk::PipelineStageFlags::TRANSFER,
-                        vk::ImageSubresourceRange::default()
-                            .aspect_mask(vk::ImageAspectFlags::COLOR)
-                            .base_array_layer(0u32)
-                            .layer_count(1u32)
-                            .base_mip_level(0u32)
-                            .level_count(1u32),
-                    );
-                    // Transition new image for transfer src
-                    insert_image_memory_barrier(
-                        &self.device,
-                        &cmd_buff,
-                        &texture_image,
-                        vk::QUEUE_FAMILY_IGNORED,
-                        vk::QUEUE_FAMILY_IGNORED,
-                        vk::AccessFlags::SHADER_READ,
-                        vk::AccessFlags::TRANSFER_READ,
-                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                        vk::PipelineStageFlags::FRAGMENT_SHADER,
-                        vk::PipelineStageFlags::TRANSFER,
-                        vk::ImageSubresourceRange::default()
-                            .aspect_mask(vk::ImageAspectFlags::COLOR)
-                            .base_array_layer(0u32)
-                            .layer_count(1u32)
-                            .base_mip_level(0u32)
-                            .level_count(1u32),
-                    );
-                    let top_left = vk::Offset3D {
-                        x: pos[0] as i32,
-                        y: pos[1] as i32,
-                        z: 0,
-                    };
-                    let bottom_right = vk::Offset3D {
-                        x: pos[0] as i32 + delta.image.width() as i32,
-                        y: pos[1] as i32 + delta.image.height() as i32,
-                        z: 1,
-                    };
-
-                    let region = vk::ImageBlit {
-                        src_subresource: vk::ImageSubresourceLayers {
-                            aspect_mask: vk::ImageAspectFlags::COLOR,
-                            mip_level: 0,
-                            base_array_layer: 0,
-                            layer_count: 1,
-                        },
-                        src_offsets: [
-                            vk::Offset3D { x: 0, y: 0, z: 0 },
-                            vk::Offset3D {
-                                x: info.extent.width as i32,
-                                y: info.extent.height as i32,
-                                z: info.extent.depth as i32,
-                            },
-                        ],
-                        dst_subresource: vk::ImageSubresourceLayers {
-                            aspect_mask: vk::ImageAspectFlags::COLOR,
-                            mip_level: 0,
-                            base_array_layer: 0,
-                            layer_count: 1,
-                        },
-                        dst_offsets: [top_left, bottom_right],
-                    };
-                    self.device.cmd_blit_image(
-                        cmd_buff,
-                        texture_image,
-                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                        *existing_texture,
-                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        &[region],
-                        vk::Filter::NEAREST,
-                    );
+    // Get the descriptor set a mesh is drawn with, if its texture is registered.
+    fn mesh_descriptor_set(&self, texture_id: TextureId) -> Option<vk::DescriptorSet> {
+        match texture_id {
+            TextureId::Managed(_) => self
+                .texture_desc_sets
+                .get(&texture_id)
+                .map(|descriptor_set| descriptor_set.set),
+            TextureId::User(_) => self.user_textures.get(texture_id),
+        }
+    }
 
-                    // Transition existing image for shader read
-                    insert_image_memory_barrier(
-                        &self.device,
-                        &cmd_buff,
-                        &existing_texture,
-                        vk::QUEUE_FAMILY_IGNORED,
-                        vk::QUEUE_FAMILY_IGNORED,
-                        vk::AccessFlags::TRANSFER_WRITE,
-                        vk::AccessFlags::SHADER_READ,
-                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                        vk::PipelineStageFlags::TRANSFER,
-                        vk::PipelineStageFlags::FRAGMENT_SHADER,
-                        vk::ImageSubresourceRange::default()
-                            .aspect_mask(vk::ImageAspectFlags::COLOR)
-                            .base_array_layer(0u32)
-                            .layer_count(1u32)
-                            .base_mip_level(0u32)
-                            .level_count(1u32),
-                    );
-                    self.device.end_command_buffer(cmd_buff).unwrap();
-                    let cmd_buffs = [cmd_buff];
-                    let submit_infos = [vk::SubmitInfo::default().command_buffers(&cmd_buffs)];
-                    self.device
-                        .queue_submit(self.queue, &submit_infos, cmd_buff_fence)
-                        .unwrap();
-                    self.device
-                        .wait_for_fences(&[cmd_buff_fence], true, u64::MAX)
-                        .unwrap();
-
-                    // destroy texture_image and view
-                    self.device.destroy_image(texture_image, None);
-                    self.device.destroy_image_view(texture_image_view, None);
-                    self.allocator.free(texture_allocation).unwrap();
-                }
-            } else {
-                return;
-            }
-        } else {
-            // Otherwise save the newly created texture
+    // Stop tracking a managed texture and destroy it once the GPU has finished the current frame.
+    fn retire_texture(&mut self, texture_id: TextureId) {
+        let image = self.texture_images.remove(&texture_id);
+        let allocation = self.texture_allocations.remove(&texture_id);
+        let image_view = self.texture_image_views.remove(&texture_id);
+        let descriptor_set = self.texture_desc_sets.remove(&texture_id);
+        if let (Some(image), Some(allocation), Some(image_view), Some(descriptor_set)) =
+            (image, allocation, image_view, descriptor_set)
+        {
+            self.frame_buffers[self.frame_index]
+                .retired_textures
+                .push(RetiredTexture {
+                    image,
+                    image_view,
+                    allocation,
+                    descriptor_set,
+                });
+        }
+    }
 
-            // update dsc set
-            let dsc_set = {
-                let dsc_alloc_info = vk::DescriptorSetAllocateInfo::default()
-                    .descriptor_pool(self.descriptor_pool)
-                    .set_layouts(std::slice::from_ref(&self.descriptor_set_layouts[0]));
-                unsafe {
-                    self.device
-                        .allocate_descriptor_sets(&dsc_alloc_info)
-                        .unwrap()[0]
-                }
-            };
-            let image_info = vk::DescriptorImageInfo::default()
-                .image_view(texture_image_view)
-                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .sampler(self.sampler);
-            let dsc_writes = [vk::WriteDescriptorSet::default()
-                .dst_set(dsc_set)
-                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                .dst_array_element(0_u32)
-                .dst_binding(0_u32)
-                .image_info(std::slice::from_ref(&image_info))];
-            unsafe {
-                self.device.update_descriptor_sets(&dsc_writes, &[]);
-            }
-            // register new texture
-            self.texture_images.insert(texture_id, texture_image);
-            self.texture_allocations
-                .insert(texture_id, texture_allocation);
-            self.texture_image_views
-                .insert(texture_id, texture_image_view);
-            self.texture_desc_sets.insert(texture_id, dsc_set);
-        }
-        // cleanup
+    // Record the copy of a staged region into a texture in `TRANSFER_DST_OPTIMAL` layout.
+    fn copy_to_texture(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        staging_buffer: vk::Buffer,
+        buffer_offset: vk::DeviceSize,
+        texture_image: vk::Image,
+        image_offset: vk::Offset3D,
+        image_extent: vk::Extent3D,
+    ) {
+        let region = vk::BufferImageCopy::default()
+            .buffer_offset(buffer_offset)
+            .buffer_row_length(image_extent.width)
+            .buffer_image_height(image_extent.height)
+            .image_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .mip_level(0),
+            )
+            .image_offset(image_offset)
+            .image_extent(image_extent);
         unsafe {
-            self.device.destroy_buffer(staging_buffer, None);
-            self.allocator.free(staging_allocation).unwrap();
-            self.device.destroy_command_pool(cmd_pool, None);
-            self.device.destroy_fence(cmd_buff_fence, None);
+            self.device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging_buffer,
+                texture_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+            );
         }
     }
 
@@ -1194,13 +2869,20 @@ impl<A: AllocatorTrait> Integration<A> {
         physical_height: u32,
         swapchain: vk::SwapchainKHR,
         surface_format: vk::SurfaceFormatKHR,
-    ) {
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        let swapchain_loader = self
+            .swapchain_loader
+            .as_ref()
+            .ok_or(IntegrationError::Misuse(
+                "Headless integrations have no swapchain",
+            ))?;
+        let swap_images = unsafe { swapchain_loader.get_swapchain_images(swapchain) }?;
         self.physical_width = physical_width;
         self.physical_height = physical_height;
 
         // release vk objects to be regenerated.
         unsafe {
-            self.device.destroy_render_pass(self.render_pass, None);
             self.device.destroy_pipeline(self.pipeline, None);
             for &image_view in self.framebuffer_color_image_views.iter() {
                 self.device.destroy_image_view(image_view, None);
@@ -1210,224 +2892,406 @@ impl<A: AllocatorTrait> Integration<A> {
             }
         }
 
-        // swap images
-        let swap_images = unsafe { self.swapchain_loader.get_swapchain_images(swapchain) }
-            .expect("Failed to get swapchain images.");
-
-        // Recreate render pass for update surface format
-        self.render_pass = unsafe {
-            self.device.create_render_pass(
-                &vk::RenderPassCreateInfo::default()
-                    .attachments(&[vk::AttachmentDescription::default()
-                        .format(surface_format.format)
-                        .samples(vk::SampleCountFlags::TYPE_1)
-                        .load_op(vk::AttachmentLoadOp::LOAD)
-                        .store_op(vk::AttachmentStoreOp::STORE)
-                        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-                        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-                        .initial_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-                        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)])
-                    .subpasses(&[vk::SubpassDescription::default()
-                        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-                        .color_attachments(&[vk::AttachmentReference::default()
-                            .attachment(0)
-                            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)])])
-                    .dependencies(&[vk::SubpassDependency::default()
-                        .src_subpass(vk::SUBPASS_EXTERNAL)
-                        .dst_subpass(0)
-                        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-                        .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-                        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-                        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)]),
-                None,
-            )
+        // Get render pass for update surface format
+        self.swapchain_format = surface_format.format;
+        if self.rendering_mode == RenderingMode::RenderPass {
+            self.render_pass = self.cached_render_pass(
+                surface_format.format,
+                self.target_desc.load.load_op(),
+                self.target_desc.initial_layout,
+                self.target_desc.final_layout,
+            )?;
         }
-        .expect("Failed to create render pass.");
 
         // Recreate pipeline for update render pass
-        self.pipeline = {
-            let bindings = [vk::VertexInputBindingDescription::default()
-                .binding(0)
-                .input_rate(vk::VertexInputRate::VERTEX)
-                .stride(5 * std::mem::size_of::<f32>() as u32)];
-            let attributes = [
-                // position
-                vk::VertexInputAttributeDescription::default()
-                    .binding(0)
-                    .offset(0)
-                    .location(0)
-                    .format(vk::Format::R32G32_SFLOAT),
-                // uv
-                vk::VertexInputAttributeDescription::default()
-                    .binding(0)
-                    .offset(8)
-                    .location(1)
-                    .format(vk::Format::R32G32_SFLOAT),
-                // color
-                vk::VertexInputAttributeDescription::default()
-                    .binding(0)
-                    .offset(16)
-                    .location(2)
-                    .format(vk::Format::R8G8B8A8_UNORM),
-            ];
-
-            let vertex_shader_module = {
-                let bytes_code = include_bytes!("shaders/spv/vert.spv");
-                let shader_module_create_info = vk::ShaderModuleCreateInfo {
-                    code_size: bytes_code.len(),
-                    p_code: bytes_code.as_ptr() as *const u32,
-                    ..Default::default()
-                };
-                unsafe {
-                    self.device
-                        .create_shader_module(&shader_module_create_info, None)
-                }
-                .expect("Failed to create vertex shader module.")
-            };
-            let fragment_shader_module = {
-                let bytes_code = include_bytes!("shaders/spv/frag.spv");
-                let shader_module_create_info = vk::ShaderModuleCreateInfo {
-                    code_size: bytes_code.len(),
-                    p_code: bytes_code.as_ptr() as *const u32,
-                    ..Default::default()
-                };
+        self.pipeline = create_pipeline(
+            &self.device,
+            self.pipeline_layout,
+            self.render_pass,
+            surface_format.format,
+            self.sample_count,
+        )?;
+
+        // Destroy multisampled images that no target framebuffer or viewport uses anymore
+        for key in self.msaa_images.keys().copied().collect::<Vec<_>>() {
+            let used = self
+                .target_framebuffers
+                .values()
+                .any(|cached| (cached.format, cached.extent) == key)
+                || self.viewports.values().any(|viewport| {
+                    (viewport.window.surface_format.format, viewport.extent()) == key
+                });
+            if !used {
+                let msaa_image = self.msaa_images.remove(&key).unwrap();
                 unsafe {
-                    self.device
-                        .create_shader_module(&shader_module_create_info, None)
+                    destroy_attachment_image(&self.device, &self.allocator, msaa_image)?;
                 }
-                .expect("Failed to create fragment shader module.")
-            };
-            let main_function_name = CString::new("main").unwrap();
-            let pipeline_shader_stages = [
-                vk::PipelineShaderStageCreateInfo::default()
-                    .stage(vk::ShaderStageFlags::VERTEX)
-                    .module(vertex_shader_module)
-                    .name(&main_function_name),
-                vk::PipelineShaderStageCreateInfo::default()
-                    .stage(vk::ShaderStageFlags::FRAGMENT)
-                    .module(fragment_shader_module)
-                    .name(&main_function_name),
-            ];
-
-            let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::default()
-                .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
-            let viewport_info = vk::PipelineViewportStateCreateInfo::default()
-                .viewport_count(1)
-                .scissor_count(1);
-            let rasterization_info = vk::PipelineRasterizationStateCreateInfo::default()
-                .depth_clamp_enable(false)
-                .rasterizer_discard_enable(false)
-                .polygon_mode(vk::PolygonMode::FILL)
-                .cull_mode(vk::CullModeFlags::NONE)
-                .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-                .depth_bias_enable(false)
-                .line_width(1.0);
-            let stencil_op = vk::StencilOpState::default()
-                .fail_op(vk::StencilOp::KEEP)
-                .pass_op(vk::StencilOp::KEEP)
-                .compare_op(vk::CompareOp::ALWAYS);
-            let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::default()
-                .depth_test_enable(true)
-                .depth_write_enable(true)
-                .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
-                .depth_bounds_test_enable(false)
-                .stencil_test_enable(false)
-                .front(stencil_op)
-                .back(stencil_op);
-            let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::default()
-                .color_write_mask(
-                    vk::ColorComponentFlags::R
-                        | vk::ColorComponentFlags::G
-                        | vk::ColorComponentFlags::B
-                        | vk::ColorComponentFlags::A,
-                )
-                .blend_enable(true)
-                .src_color_blend_factor(vk::BlendFactor::ONE)
-                .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)];
-            let color_blend_info = vk::PipelineColorBlendStateCreateInfo::default()
-                .attachments(&color_blend_attachments);
-            let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-            let dynamic_state_info =
-                vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
-            let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default()
-                .vertex_attribute_descriptions(&attributes)
-                .vertex_binding_descriptions(&bindings);
-            let multisample_info = vk::PipelineMultisampleStateCreateInfo::default()
-                .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-
-            let pipeline_create_info = [vk::GraphicsPipelineCreateInfo::default()
-                .stages(&pipeline_shader_stages)
-                .vertex_input_state(&vertex_input_state)
-                .input_assembly_state(&input_assembly_info)
-                .viewport_state(&viewport_info)
-                .rasterization_state(&rasterization_info)
-                .multisample_state(&multisample_info)
-                .depth_stencil_state(&depth_stencil_info)
-                .color_blend_state(&color_blend_info)
-                .dynamic_state(&dynamic_state_info)
-                .layout(self.pipeline_layout)
-                .render_pass(self.render_pass)
-                .subpass(0)];
-
-            let pipeline = unsafe {
-                self.device.create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &pipeline_create_info,
-                    None,
-                )
-            }
-            .expect("Failed to create graphics pipeline")[0];
-            unsafe {
-                self.device
-                    .destroy_shader_module(vertex_shader_module, None);
-                self.device
-                    .destroy_shader_module(fragment_shader_module, None);
             }
-            pipeline
-        };
+        }
 
         // Recreate color image views for new framebuffers
-        self.framebuffer_color_image_views = swap_images
-            .iter()
-            .map(|swapchain_image| unsafe {
-                self.device
-                    .create_image_view(
-                        &vk::ImageViewCreateInfo::default()
-                            .image(swapchain_image.clone())
-                            .view_type(vk::ImageViewType::TYPE_2D)
-                            .format(surface_format.format)
-                            .subresource_range(
-                                vk::ImageSubresourceRange::default()
-                                    .aspect_mask(vk::ImageAspectFlags::COLOR)
-                                    .base_mip_level(0)
-                                    .level_count(1)
-                                    .base_array_layer(0)
-                                    .layer_count(1),
-                            ),
-                        None,
-                    )
-                    .expect("Failed to create image view.")
-            })
-            .collect::<Vec<_>>();
+        self.framebuffer_color_image_views =
+            create_swapchain_image_views(&self.device, &swap_images, surface_format.format)?;
         // Recreate framebuffers for new swapchain
-        self.framebuffers = self
-            .framebuffer_color_image_views
-            .iter()
-            .map(|&image_views| unsafe {
-                let attachments = &[image_views];
-                self.device
-                    .create_framebuffer(
-                        &vk::FramebufferCreateInfo::default()
-                            .render_pass(self.render_pass)
-                            .attachments(attachments)
-                            .width(physical_width)
-                            .height(physical_height)
-                            .layers(1),
-                        None,
-                    )
-                    .expect("Failed to create framebuffer.")
-            })
+        self.framebuffers = self.create_swapchain_framebuffers()?;
+        self.swapchain_images = swap_images;
+        Ok(())
+    }
+
+    /// Set whether egui shows deferred viewports in their own native windows.
+    ///
+    /// Disabled by default, which embeds viewports in their parent viewport. When enabled, pass every
+    /// [`egui::FullOutput`] to [`Self::update_viewports`], and run and paint each viewport with
+    /// [`Self::run_viewport`] and [`Self::paint_viewport`]. Immediate viewports are always embedded.
+    pub fn set_multiple_viewports(&mut self, enabled: bool) {
+        self.context.set_embed_viewports(!enabled);
+    }
+
+    /// Create, update and close viewports as requested by the output of a frame.
+    ///
+    /// `create_viewport` is called for each new viewport with its builder and must return the window and swapchain
+    /// the application created for it; an error it returns is returned from here, and the viewport is created by
+    /// a later call. `destroy_viewport` is called with the window of each closed viewport once the device is
+    /// idle, so the application can destroy the swapchain and surface.
+    pub fn update_viewports<E: From<IntegrationError>>(
+        &mut self,
+        viewport_output: &ViewportIdMap<ViewportOutput>,
+        mut create_viewport: impl FnMut(ViewportId, &ViewportBuilder) -> Result<ViewportWindow, E>,
+        destroy_viewport: impl FnMut(ViewportId, ViewportWindow),
+    ) -> Result<(), E> {
+        self.check_not_destroyed()?;
+        let closed = self
+            .viewports
+            .keys()
+            .filter(|viewport_id| !viewport_output.contains_key(viewport_id))
+            .copied()
             .collect::<Vec<_>>();
+        self.close_viewports(&closed, destroy_viewport)?;
+
+        for (&viewport_id, output) in viewport_output {
+            if viewport_id == ViewportId::ROOT {
+                continue;
+            }
+            if !self.viewports.contains_key(&viewport_id) {
+                let window = create_viewport(viewport_id, &output.builder)?;
+                self.open_viewport(viewport_id, window, output.builder.clone())?;
+            }
+            let viewport = self.viewports.get_mut(&viewport_id).unwrap();
+            viewport.viewport_ui_cb.clone_from(&output.viewport_ui_cb);
+            let (mut commands, _) = viewport.builder.patch(output.builder.clone());
+            commands.extend(output.commands.iter().cloned());
+            let focused = viewport.info.focused.unwrap_or(false);
+            egui_winit::process_viewport_commands(
+                &self.context,
+                &mut viewport.info,
+                commands,
+                &viewport.window.window,
+                focused,
+                &mut false,
+            );
+        }
+        Ok(())
+    }
+
+    /// Close all viewports other than the root viewport, e.g. before destroying the integration.
+    ///
+    /// `destroy_viewport` is called with the window of each viewport once the device is idle.
+    pub fn close_all_viewports(
+        &mut self,
+        destroy_viewport: impl FnMut(ViewportId, ViewportWindow),
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        let closed = self.viewports.keys().copied().collect::<Vec<_>>();
+        self.close_viewports(&closed, destroy_viewport)
+    }
+
+    /// Get the viewport a window belongs to, if it is a viewport other than the root viewport.
+    pub fn viewport_id(&self, window_id: WindowId) -> Option<ViewportId> {
+        self.viewports
+            .iter()
+            .find(|(_, viewport)| viewport.window.window.id() == window_id)
+            .map(|(&viewport_id, _)| viewport_id)
+    }
+
+    /// Get the window and swapchain of a viewport other than the root viewport.
+    pub fn viewport_window(&self, viewport_id: ViewportId) -> Option<&ViewportWindow> {
+        self.viewports
+            .get(&viewport_id)
+            .map(|viewport| &viewport.window)
+    }
+
+    /// handling winit event of a viewport other than the root viewport.
+    pub fn handle_viewport_event(
+        &mut self,
+        viewport_id: ViewportId,
+        winit_event: &egui_winit::winit::event::WindowEvent,
+    ) -> Result<EventResponse, IntegrationError> {
+        self.check_not_destroyed()?;
+        let viewport = self
+            .viewports
+            .get_mut(&viewport_id)
+            .ok_or(IntegrationError::Misuse("Unknown viewport"))?;
+        match winit_event {
+            egui_winit::winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                viewport.scale_factor = *scale_factor;
+            }
+            egui_winit::winit::event::WindowEvent::CloseRequested => {
+                viewport.info.events.push(egui::ViewportEvent::Close);
+            }
+            _ => (),
+        }
+
+        Ok(viewport
+            .egui_winit
+            .on_window_event(&viewport.window.window, winit_event))
+    }
+
+    /// Run the ui of a deferred viewport other than the root viewport.
+    ///
+    /// Call this after [`Self::end_frame`] of the root viewport, then pass the output to [`Self::update_viewports`]
+    /// and paint it with [`Self::paint_viewport`] in the same frame.
+    pub fn run_viewport(
+        &mut self,
+        viewport_id: ViewportId,
+    ) -> Result<egui::FullOutput, IntegrationError> {
+        self.check_not_destroyed()?;
+        let viewport = self
+            .viewports
+            .get_mut(&viewport_id)
+            .ok_or(IntegrationError::Misuse("Unknown viewport"))?;
+        egui_winit::update_viewport_info(
+            &mut viewport.info,
+            &self.context,
+            &viewport.window.window,
+        );
+        viewport
+            .egui_winit
+            .egui_input_mut()
+            .viewports
+            .insert(viewport_id, viewport.info.clone());
+        viewport.info.events.clear();
+        let raw_input = viewport.egui_winit.take_egui_input(&viewport.window.window);
+
+        let viewport_ui_cb = viewport.viewport_ui_cb.clone();
+        let output = self.context.run(raw_input, |context| {
+            if let Some(viewport_ui_cb) = viewport_ui_cb {
+                viewport_ui_cb(context);
+            }
+        });
+
+        let viewport = self.viewports.get_mut(&viewport_id).unwrap();
+        viewport
+            .egui_winit
+            .handle_platform_output(&viewport.window.window, output.platform_output.clone());
+
+        Ok(output)
+    }
+
+    /// Record paint commands for a swapchain image of a viewport other than the root viewport.
+    ///
+    /// The viewport's swapchain image is loaded and transitioned like the root swapchain image.
+    /// All viewports painted in a frame share the buffers of the frame in flight selected for the root viewport.
+    pub fn paint_viewport(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        viewport_id: ViewportId,
+        swapchain_image_index: usize,
+        clipped_meshes: Vec<egui::ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<Option<TimelineSignal>, IntegrationError> {
+        self.check_not_destroyed()?;
+        let viewport = self
+            .viewports
+            .get(&viewport_id)
+            .ok_or(IntegrationError::Misuse("Unknown viewport"))?;
+        let format = viewport.window.surface_format.format;
+        let extent = viewport.extent();
+        let pixels_per_point = viewport.scale_factor as f32;
+        let (Some(&image), Some(&image_view)) = (
+            viewport.swapchain_images.get(swapchain_image_index),
+            viewport.image_views.get(swapchain_image_index),
+        ) else {
+            return Err(IntegrationError::Misuse(
+                "swapchain_image_index is out of range",
+            ));
+        };
+        let framebuffer = viewport.framebuffers.get(swapchain_image_index).copied();
+
+        let render_pass = match self.rendering_mode {
+            RenderingMode::RenderPass => self.cached_render_pass(
+                format,
+                self.target_desc.load.load_op(),
+                self.target_desc.initial_layout,
+                self.target_desc.final_layout,
+            )?,
+            RenderingMode::DynamicRendering => vk::RenderPass::null(),
+        };
+        let pipeline = self.target_pipeline(render_pass, format)?;
+        let attachment = match self.rendering_mode {
+            RenderingMode::RenderPass => TargetAttachment::Framebuffer {
+                render_pass,
+                framebuffer: framebuffer.ok_or(IntegrationError::Misuse(
+                    "The viewport has no framebuffer for swapchain_image_index",
+                ))?,
+            },
+            RenderingMode::DynamicRendering => TargetAttachment::ImageView {
+                image_view,
+                msaa: self.msaa_attachment(format, extent)?,
+                transition: Some((
+                    image,
+                    self.target_desc.initial_layout,
+                    self.target_desc.final_layout,
+                )),
+            },
+        };
+        let target = RenderTarget {
+            extent,
+            format,
+            pixels_per_point,
+            pipeline,
+            load: self.target_desc.load,
+            attachment,
+        };
+        self.paint_target(command_buffer, None, target, clipped_meshes, textures_delta)
+    }
+
+    /// Update the swapchain of a viewport other than the root viewport, e.g. after its window was resized.
+    ///
+    /// The GPU must have finished with the previous swapchain images of the viewport.
+    pub fn update_viewport_swapchain(
+        &mut self,
+        viewport_id: ViewportId,
+        physical_width: u32,
+        physical_height: u32,
+        swapchain: vk::SwapchainKHR,
+        surface_format: vk::SurfaceFormatKHR,
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        let swapchain_loader = self
+            .swapchain_loader
+            .as_ref()
+            .ok_or(IntegrationError::Misuse(
+                "Headless integrations have no swapchain",
+            ))?;
+        let viewport = self
+            .viewports
+            .get_mut(&viewport_id)
+            .ok_or(IntegrationError::Misuse("Unknown viewport"))?;
+        unsafe {
+            for image_view in viewport.image_views.drain(..) {
+                self.device.destroy_image_view(image_view, None);
+            }
+        }
+        viewport.window.swapchain = swapchain;
+        viewport.window.surface_format = surface_format;
+        viewport.physical_width = physical_width;
+        viewport.physical_height = physical_height;
+        viewport.swapchain_images = unsafe { swapchain_loader.get_swapchain_images(swapchain) }?;
+        viewport.image_views = create_swapchain_image_views(
+            &self.device,
+            &viewport.swapchain_images,
+            surface_format.format,
+        )?;
+        self.recreate_viewport_framebuffers(viewport_id)
+    }
+
+    // Start tracking a viewport whose window the application has created.
+    fn open_viewport(
+        &mut self,
+        viewport_id: ViewportId,
+        window: ViewportWindow,
+        builder: ViewportBuilder,
+    ) -> Result<(), IntegrationError> {
+        let scale_factor = window.window.scale_factor();
+        let size = window.window.inner_size();
+        let egui_winit = egui_winit::State::new(
+            self.context.clone(),
+            viewport_id,
+            &window.window,
+            Some(scale_factor as f32),
+            Some(self.max_texture_side),
+        );
+        let swapchain = window.swapchain;
+        let format = window.surface_format.format;
+        // Register the window first, so that it is handed to `destroy_viewport` even if the rest fails
+        self.viewports.insert(
+            viewport_id,
+            ViewportState {
+                window,
+                egui_winit,
+                info: ViewportInfo::default(),
+                builder,
+                viewport_ui_cb: None,
+                scale_factor,
+                physical_width: size.width,
+                physical_height: size.height,
+                swapchain_images: vec![],
+                image_views: vec![],
+                framebuffers: vec![],
+            },
+        );
+        let swapchain_images = unsafe {
+            self.swapchain_loader
+                .as_ref()
+                .ok_or(IntegrationError::Misuse(
+                    "Headless integrations have no swapchain",
+                ))?
+                .get_swapchain_images(swapchain)?
+        };
+        let image_views = create_swapchain_image_views(&self.device, &swapchain_images, format)?;
+        let viewport = self.viewports.get_mut(&viewport_id).unwrap();
+        viewport.swapchain_images = swapchain_images;
+        viewport.image_views = image_views;
+        self.recreate_viewport_framebuffers(viewport_id)
+    }
+
+    // Destroy the framebuffers of a viewport and create them for its current swapchain images.
+    fn recreate_viewport_framebuffers(
+        &mut self,
+        viewport_id: ViewportId,
+    ) -> Result<(), IntegrationError> {
+        let viewport = &self.viewports[&viewport_id];
+        let image_views = viewport.image_views.clone();
+        let format = viewport.window.surface_format.format;
+        let extent = viewport.extent();
+        let framebuffers = self.create_framebuffers(&image_views, format, extent)?;
+        let viewport = self.viewports.get_mut(&viewport_id).unwrap();
+        for framebuffer in std::mem::replace(&mut viewport.framebuffers, framebuffers) {
+            unsafe {
+                self.device.destroy_framebuffer(framebuffer, None);
+            }
+        }
+        Ok(())
+    }
+
+    // Stop tracking viewports and hand their windows back to the application once the GPU has finished with them.
+    fn close_viewports(
+        &mut self,
+        viewport_ids: &[ViewportId],
+        mut destroy_viewport: impl FnMut(ViewportId, ViewportWindow),
+    ) -> Result<(), IntegrationError> {
+        if viewport_ids.is_empty() {
+            return Ok(());
+        }
+        // The application may also present the swapchains on queues other than the integration's
+        unsafe {
+            self.device.device_wait_idle()?;
+        }
+        for viewport_id in viewport_ids {
+            let Some(viewport) = self.viewports.remove(viewport_id) else {
+                continue;
+            };
+            unsafe {
+                for framebuffer in viewport.framebuffers {
+                    self.device.destroy_framebuffer(framebuffer, None);
+                }
+                for image_view in viewport.image_views {
+                    self.device.destroy_image_view(image_view, None);
+                }
+            }
+            destroy_viewport(*viewport_id, viewport.window);
+        }
+        Ok(())
     }
 
     /// Registering user texture.
@@ -1435,7 +3299,7 @@ impl<A: AllocatorTrait> Integration<A> {
     /// Pass the Vulkan ImageView and Sampler.
     /// `image_view`'s image layout must be `SHADER_READ_ONLY_OPTIMAL`.
     ///
-    /// UserTexture needs to be unregistered when it is no longer needed.
+    /// UserTexture needs to be unregistered when it is no longer needed, or use [`Self::register_user_texture_handle`].
     ///
     /// # Example
     /// ```sh
@@ -1446,36 +3310,17 @@ impl<A: AllocatorTrait> Integration<A> {
         &mut self,
         image_view: vk::ImageView,
         sampler: vk::Sampler,
-    ) -> egui::TextureId {
-        // get texture id
-        let mut id = None;
-        for (i, user_texture) in self.user_textures.iter().enumerate() {
-            if user_texture.is_none() {
-                id = Some(i as u64);
-                break;
-            }
-        }
-        let id = if let Some(i) = id {
-            i
-        } else {
-            self.user_textures.len() as u64
-        };
-
+    ) -> Result<egui::TextureId, IntegrationError> {
+        self.check_not_destroyed()?;
         // allocate and update descriptor set
-        let layouts = [self.user_texture_layout];
-        let descriptor_set = unsafe {
-            self.device.allocate_descriptor_sets(
-                &vk::DescriptorSetAllocateInfo::default()
-                    .descriptor_pool(self.descriptor_pool)
-                    .set_layouts(&layouts),
-            )
-        }
-        .expect("Failed to create descriptor sets.")[0];
+        let descriptor_set = self
+            .descriptor_pools
+            .allocate(&self.device, self.user_texture_layout)?;
         unsafe {
             self.device.update_descriptor_sets(
                 &[vk::WriteDescriptorSet::default()
                     .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .dst_set(descriptor_set)
+                    .dst_set(descriptor_set.set)
                     .image_info(&[vk::DescriptorImageInfo::default()
                         .image_view(image_view)
                         .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
@@ -1485,61 +3330,81 @@ impl<A: AllocatorTrait> Integration<A> {
             );
         }
 
-        if id == self.user_textures.len() as u64 {
-            self.user_textures.push(Some(descriptor_set));
-        } else {
-            self.user_textures[id as usize] = Some(descriptor_set);
-        }
+        Ok(self.user_textures.insert(descriptor_set))
+    }
 
-        egui::TextureId::User(id)
+    /// Register a user texture that is unregistered when the returned handle is dropped.
+    ///
+    /// See [`Self::register_user_texture`].
+    pub fn register_user_texture_handle(
+        &mut self,
+        image_view: vk::ImageView,
+        sampler: vk::Sampler,
+    ) -> Result<UserTextureHandle, IntegrationError> {
+        self.check_not_destroyed()?;
+        let texture_id = self.register_user_texture(image_view, sampler)?;
+        Ok(self.user_textures.handle(texture_id))
     }
 
     /// Unregister user texture.
     ///
-    /// The internal texture (egui::TextureId::Egui) cannot be unregistered.
-    pub fn unregister_user_texture(&mut self, texture_id: egui::TextureId) {
-        if let egui::TextureId::User(id) = texture_id {
-            if let Some(descriptor_set) = self.user_textures[id as usize] {
-                unsafe {
-                    self.device
-                        .free_descriptor_sets(self.descriptor_pool, &[descriptor_set])
-                        .expect("Failed to free descriptor sets.");
-                }
-                self.user_textures[id as usize] = None;
-            }
-        } else {
-            eprintln!("The internal texture cannot be unregistered; please pass the texture ID of UserTexture.");
-            return;
-        }
+    /// The id becomes invalid immediately, even if its slot is reused by a later registration, while the
+    /// descriptor set is freed once the GPU has finished the frames that may use it. Meshes that
+    /// still use the id are skipped.
+    /// The internal texture (egui::TextureId::Egui) and ids that are already unregistered cannot be unregistered.
+    pub fn unregister_user_texture(
+        &mut self,
+        texture_id: egui::TextureId,
+    ) -> Result<(), IntegrationError> {
+        self.check_not_destroyed()?;
+        self.user_textures.remove(texture_id)
     }
 
     /// destroy vk objects.
     ///
-    /// # Unsafe
+    /// Dropping the integration does this too, so call it only to destroy the objects early or to handle errors.
+    /// All objects are destroyed even if an error occurs; the first error is returned.
+    /// Destroying the integration twice returns [`IntegrationError::Misuse`], as do all fallible methods called
+    /// afterwards.
+    /// The windows of open viewports are dropped without calling the `destroy_viewport` callback, so call
+    /// [`Self::close_all_viewports`] first to destroy their surfaces and swapchains.
+    ///
+    /// # Safety
     /// This method release vk objects memory that is not managed by Rust.
-    pub unsafe fn destroy(&mut self) {
+    /// The integration must not be used for painting afterwards.
+    pub unsafe fn destroy(&mut self) -> Result<(), IntegrationError> {
+        if self.destroyed {
+            return Err(IntegrationError::Misuse(
+                "The integration has already been destroyed",
+            ));
+        }
+        self.destroyed = true;
+
+        // Wait for submitted paint commands and uploads
+        let mut result = self
+            .device
+            .queue_wait_idle(self.queue)
+            .map_err(IntegrationError::from);
+        if let Some(transfer_queue) = self.transfer_queue.take() {
+            result = result.and(
+                self.device
+                    .queue_wait_idle(transfer_queue.queue)
+                    .map_err(IntegrationError::from),
+            );
+            transfer_queue.destroy(&self.device);
+        }
+
+        self.callback_resources.destroy(&self.device);
+
         self.device
             .destroy_descriptor_set_layout(self.user_texture_layout, None);
 
-        for (buffer, allocation) in self
-            .index_buffers
-            .drain(0..)
-            .zip(self.index_buffer_allocations.drain(0..))
-        {
-            self.device.destroy_buffer(buffer, None);
-            self.allocator
-                .free(allocation)
-                .expect("Failed to free allocation");
-        }
-        for (buffer, allocation) in self
-            .vertex_buffers
-            .drain(0..)
-            .zip(self.vertex_buffer_allocations.drain(0..))
-        {
-            self.device.destroy_buffer(buffer, None);
-            self.allocator
-                .free(allocation)
-                .expect("Failed to free allocation");
+        for frame in self.frame_buffers.drain(..) {
+            result = result.and(frame.destroy(
+                &self.device,
+                &self.allocator,
+                &mut self.descriptor_pools,
+            ));
         }
         for &image_view in self.framebuffer_color_image_views.iter() {
             self.device.destroy_image_view(image_view, None);
@@ -1547,8 +3412,48 @@ impl<A: AllocatorTrait> Integration<A> {
         for &framebuffer in self.framebuffers.iter() {
             self.device.destroy_framebuffer(framebuffer, None);
         }
-        self.device.destroy_render_pass(self.render_pass, None);
-        self.device.destroy_sampler(self.sampler, None);
+        for (_, cached) in self.target_framebuffers.drain() {
+            self.device.destroy_framebuffer(cached.framebuffer, None);
+        }
+        for (_, viewport) in self.viewports.drain() {
+            for framebuffer in viewport.framebuffers {
+                self.device.destroy_framebuffer(framebuffer, None);
+            }
+            for image_view in viewport.image_views {
+                self.device.destroy_image_view(image_view, None);
+            }
+        }
+        for (_, render_pass) in self.render_passes.drain() {
+            self.device.destroy_render_pass(render_pass, None);
+        }
+        for (_, pipeline) in self.target_pipelines.drain() {
+            self.device.destroy_pipeline(pipeline, None);
+        }
+        for (_, msaa_image) in self.msaa_images.drain() {
+            result = result.and(destroy_attachment_image(
+                &self.device,
+                &self.allocator,
+                msaa_image,
+            ));
+        }
+        if let Some(offscreen_image) = self.offscreen_image.take() {
+            result = result.and(destroy_attachment_image(
+                &self.device,
+                &self.allocator,
+                offscreen_image,
+            ));
+        }
+        if let Some(readback) = self.screenshot.take() {
+            self.device.destroy_buffer(readback.buffer, None);
+            result = result.and(
+                self.allocator
+                    .free(readback.allocation)
+                    .map_err(IntegrationError::from),
+            );
+        }
+        for (_, sampler) in self.samplers.drain() {
+            self.device.destroy_sampler(sampler, None);
+        }
         self.device.destroy_pipeline(self.pipeline, None);
         self.device
             .destroy_pipeline_layout(self.pipeline_layout, None);
@@ -1556,8 +3461,7 @@ impl<A: AllocatorTrait> Integration<A> {
             self.device
                 .destroy_descriptor_set_layout(descriptor_set_layout, None);
         }
-        self.device
-            .destroy_descriptor_pool(self.descriptor_pool, None);
+        self.descriptor_pools.destroy(&self.device);
 
         for (_texture_id, texture_image) in self.texture_images.drain() {
             self.device.destroy_image(texture_image, None);
@@ -1566,7 +3470,467 @@ impl<A: AllocatorTrait> Integration<A> {
             self.device.destroy_image_view(texture_image_view, None);
         }
         for (_texture_id, texture_allocation) in self.texture_allocations.drain() {
-            self.allocator.free(texture_allocation).unwrap();
+            result = result.and(
+                self.allocator
+                    .free(texture_allocation)
+                    .map_err(IntegrationError::from),
+            );
+        }
+        result
+    }
+
+    // Fail once the integration has been destroyed, since its vk objects are gone.
+    fn check_not_destroyed(&self) -> Result<(), IntegrationError> {
+        if self.destroyed {
+            return Err(IntegrationError::Misuse(
+                "The integration has been destroyed",
+            ));
+        }
+        Ok(())
+    }
+}
+impl<A: AllocatorTrait> Drop for Integration<A> {
+    fn drop(&mut self) {
+        if self.destroyed || !self.destroy_on_drop {
+            return;
+        }
+        if let Err(err) = unsafe { self.destroy() } {
+            eprintln!("Failed to destroy the integration: {}", err);
+        }
+    }
+}
+
+// Create the render pass egui is drawn in.
+fn create_render_pass(
+    device: &Device,
+    format: vk::Format,
+    sample_count: vk::SampleCountFlags,
+    load_op: vk::AttachmentLoadOp,
+    initial_layout: vk::ImageLayout,
+    final_layout: vk::ImageLayout,
+) -> Result<vk::RenderPass, IntegrationError> {
+    // Wait for whatever produced the image if it does not come from a previous color pass
+    let (src_stage_mask, src_access_mask) =
+        if initial_layout == vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL {
+            (
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            )
+        } else {
+            (
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::AccessFlags::MEMORY_WRITE,
+            )
+        };
+    let mut dependencies = vec![vk::SubpassDependency::default()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0)
+        .src_access_mask(src_access_mask)
+        .dst_access_mask(
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        )
+        .src_stage_mask(src_stage_mask)
+        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)];
+    // Make the result visible to whatever uses the image next, unless it is presented
+    if final_layout != vk::ImageLayout::PRESENT_SRC_KHR {
+        dependencies.push(
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(vk::PipelineStageFlags::ALL_COMMANDS),
+        );
+    }
+    let target_attachment = vk::AttachmentDescription::default()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(load_op)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(initial_layout)
+        .final_layout(final_layout);
+    let color_attachment_ref = [vk::AttachmentReference::default()
+        .attachment(0)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+    let resolve_attachment_ref = [vk::AttachmentReference::default()
+        .attachment(1)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+    let (attachments, subpass) = if sample_count == vk::SampleCountFlags::TYPE_1 {
+        (
+            vec![target_attachment],
+            vk::SubpassDescription::default()
+                .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                .color_attachments(&color_attachment_ref),
+        )
+    } else {
+        // Draw into the multisampled attachment 0 and resolve it into the target attachment 1
+        let msaa_attachment = vk::AttachmentDescription::default()
+            .format(format)
+            .samples(sample_count)
+            .load_op(load_op)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        (
+            vec![
+                msaa_attachment,
+                target_attachment.load_op(vk::AttachmentLoadOp::DONT_CARE),
+            ],
+            vk::SubpassDescription::default()
+                .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                .color_attachments(&color_attachment_ref)
+                .resolve_attachments(&resolve_attachment_ref),
+        )
+    };
+    unsafe {
+        device.create_render_pass(
+            &vk::RenderPassCreateInfo::default()
+                .attachments(&attachments)
+                .subpasses(std::slice::from_ref(&subpass))
+                .dependencies(&dependencies),
+            None,
+        )
+    }
+    .map_err(IntegrationError::from)
+}
+
+// Create a color attachment image of `format` and `extent`.
+fn create_attachment_image<A: AllocatorTrait>(
+    device: &Device,
+    allocator: &A,
+    format: vk::Format,
+    extent: vk::Extent2D,
+    sample_count: vk::SampleCountFlags,
+    usage: vk::ImageUsageFlags,
+) -> Result<AttachmentImage<A>, IntegrationError> {
+    let image = unsafe {
+        device.create_image(
+            &vk::ImageCreateInfo::default()
+                .image_type(vk::ImageType::TYPE_2D)
+                .format(format)
+                .extent(vk::Extent3D {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                })
+                .mip_levels(1)
+                .array_layers(1)
+                .samples(sample_count)
+                .tiling(vk::ImageTiling::OPTIMAL)
+                .usage(usage)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .initial_layout(vk::ImageLayout::UNDEFINED),
+            None,
+        )
+    }?;
+    let requirements = unsafe { device.get_image_memory_requirements(image) };
+    let allocation = allocator.allocate(A::AllocationCreateInfo::new(
+        requirements,
+        MemoryLocation::GpuOnly,
+        false,
+    ))?;
+    unsafe {
+        device.bind_image_memory(image, allocation.memory(), allocation.offset())?;
+    }
+    let image_view = unsafe {
+        device.create_image_view(
+            &vk::ImageViewCreateInfo::default()
+                .image(image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(format)
+                .subresource_range(color_subresource_range()),
+            None,
+        )
+    }?;
+    Ok(AttachmentImage {
+        image,
+        image_view,
+        allocation,
+    })
+}
+
+unsafe fn destroy_attachment_image<A: AllocatorTrait>(
+    device: &Device,
+    allocator: &A,
+    attachment_image: AttachmentImage<A>,
+) -> Result<(), IntegrationError> {
+    device.destroy_image_view(attachment_image.image_view, None);
+    device.destroy_image(attachment_image.image, None);
+    allocator.free(attachment_image.allocation)?;
+    Ok(())
+}
+
+// Create a sampler matching egui texture options.
+fn create_sampler(
+    device: &Device,
+    options: egui::TextureOptions,
+) -> Result<vk::Sampler, IntegrationError> {
+    let filter = |filter| match filter {
+        egui::TextureFilter::Nearest => vk::Filter::NEAREST,
+        egui::TextureFilter::Linear => vk::Filter::LINEAR,
+    };
+    let address_mode = match options.wrap_mode {
+        egui::TextureWrapMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        egui::TextureWrapMode::Repeat => vk::SamplerAddressMode::REPEAT,
+        egui::TextureWrapMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+    };
+    unsafe {
+        device.create_sampler(
+            &vk::SamplerCreateInfo::default()
+                .address_mode_u(address_mode)
+                .address_mode_v(address_mode)
+                .address_mode_w(address_mode)
+                .anisotropy_enable(false)
+                .min_filter(filter(options.minification))
+                .mag_filter(filter(options.magnification))
+                .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+                .min_lod(0.0)
+                .max_lod(vk::LOD_CLAMP_NONE),
+            None,
+        )
+    }
+    .map_err(IntegrationError::from)
+}
+
+// Whether a color attachment format encodes linear colors to sRGB on write.
+fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8_SRGB
+            | vk::Format::R8G8_SRGB
+            | vk::Format::R8G8B8_SRGB
+            | vk::Format::B8G8R8_SRGB
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_SRGB
+            | vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
+
+// Create a color image view for each swapchain image.
+fn create_swapchain_image_views(
+    device: &Device,
+    swapchain_images: &[vk::Image],
+    format: vk::Format,
+) -> Result<Vec<vk::ImageView>, IntegrationError> {
+    let mut image_views = Vec::with_capacity(swapchain_images.len());
+    for &swapchain_image in swapchain_images {
+        let image_view = unsafe {
+            device.create_image_view(
+                &vk::ImageViewCreateInfo::default()
+                    .image(swapchain_image)
+                    .view_type(vk::ImageViewType::TYPE_2D)
+                    .format(format)
+                    .subresource_range(color_subresource_range()),
+                None,
+            )
+        };
+        match image_view {
+            Ok(image_view) => image_views.push(image_view),
+            Err(err) => {
+                for image_view in image_views {
+                    unsafe { device.destroy_image_view(image_view, None) };
+                }
+                return Err(err.into());
+            }
         }
     }
+    Ok(image_views)
+}
+
+// Get the byte indices of red, green, blue and alpha in a pixel of a format that can be read back.
+// Check that an image in `layout` and of `format` can be read back.
+fn check_readback(layout: vk::ImageLayout, format: vk::Format) -> Result<(), IntegrationError> {
+    if readback_swizzle(format).is_none() {
+        return Err(IntegrationError::Misuse(
+            "Reading back images of this format is not supported",
+        ));
+    }
+    if layout == vk::ImageLayout::UNDEFINED {
+        return Err(IntegrationError::Misuse(
+            "Images in UNDEFINED layout have no contents to read back",
+        ));
+    }
+    Ok(())
+}
+
+fn readback_swizzle(format: vk::Format) -> Option<[usize; 4]> {
+    match format {
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => Some([0, 1, 2, 3]),
+        vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB => Some([2, 1, 0, 3]),
+        _ => None,
+    }
+}
+
+fn color_subresource_range() -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_array_layer(0u32)
+        .layer_count(1u32)
+        .base_mip_level(0u32)
+        .level_count(1u32)
+}
+
+// Create the egui pipeline.
+//
+// If `render_pass` is null, the pipeline is created for dynamic rendering into `format`.
+fn create_pipeline(
+    device: &Device,
+    pipeline_layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    format: vk::Format,
+    sample_count: vk::SampleCountFlags,
+) -> Result<vk::Pipeline, IntegrationError> {
+    let bindings = [vk::VertexInputBindingDescription::default()
+        .binding(0)
+        .input_rate(vk::VertexInputRate::VERTEX)
+        .stride(4 * std::mem::size_of::<f32>() as u32 + 4 * std::mem::size_of::<u8>() as u32)];
+
+    let attributes = [
+        // position
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .offset(0)
+            .location(0)
+            .format(vk::Format::R32G32_SFLOAT),
+        // uv
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .offset(8)
+            .location(1)
+            .format(vk::Format::R32G32_SFLOAT),
+        // color
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .offset(16)
+            .location(2)
+            .format(vk::Format::R8G8B8A8_UNORM),
+    ];
+
+    let vertex_shader_module = {
+        let bytes_code = include_bytes!("shaders/spv/vert.spv");
+        let shader_module_create_info = vk::ShaderModuleCreateInfo {
+            code_size: bytes_code.len(),
+            p_code: bytes_code.as_ptr() as *const u32,
+            ..Default::default()
+        };
+        unsafe { device.create_shader_module(&shader_module_create_info, None) }?
+    };
+    let fragment_shader_module = {
+        let bytes_code = include_bytes!("shaders/spv/frag.spv");
+        let shader_module_create_info = vk::ShaderModuleCreateInfo {
+            code_size: bytes_code.len(),
+            p_code: bytes_code.as_ptr() as *const u32,
+            ..Default::default()
+        };
+        unsafe { device.create_shader_module(&shader_module_create_info, None) }?
+    };
+    // egui colors are in gamma space; sRGB targets expect linear colors and encode them on write
+    let linear_output = vk::Bool32::from(is_srgb_format(format));
+    let specialization_map_entries = [vk::SpecializationMapEntry::default()
+        .constant_id(0)
+        .offset(0)
+        .size(std::mem::size_of::<vk::Bool32>())];
+    let specialization_info = vk::SpecializationInfo::default()
+        .map_entries(&specialization_map_entries)
+        .data(bytes_of(&linear_output));
+    let main_function_name = CString::new("main").unwrap();
+    let pipeline_shader_stages = [
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vertex_shader_module)
+            .name(&main_function_name),
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(fragment_shader_module)
+            .name(&main_function_name)
+            .specialization_info(&specialization_info),
+    ];
+
+    let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::default()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+    let viewport_info = vk::PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+    let rasterization_info = vk::PipelineRasterizationStateCreateInfo::default()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .depth_bias_enable(false)
+        .line_width(1.0);
+    let stencil_op = vk::StencilOpState::default()
+        .fail_op(vk::StencilOp::KEEP)
+        .pass_op(vk::StencilOp::KEEP)
+        .compare_op(vk::CompareOp::ALWAYS);
+    let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::default()
+        .depth_test_enable(false)
+        .depth_write_enable(false)
+        .depth_compare_op(vk::CompareOp::ALWAYS)
+        .depth_bounds_test_enable(false)
+        .stencil_test_enable(false)
+        .front(stencil_op)
+        .back(stencil_op);
+    let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::default()
+        .color_write_mask(
+            vk::ColorComponentFlags::R
+                | vk::ColorComponentFlags::G
+                | vk::ColorComponentFlags::B
+                | vk::ColorComponentFlags::A,
+        )
+        .blend_enable(true)
+        .src_color_blend_factor(vk::BlendFactor::ONE)
+        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+        // Accumulate coverage in the alpha channel, so that the painted image can be composited
+        .src_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_DST_ALPHA)
+        .dst_alpha_blend_factor(vk::BlendFactor::ONE)];
+    let color_blend_info =
+        vk::PipelineColorBlendStateCreateInfo::default().attachments(&color_blend_attachments);
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state_info =
+        vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default()
+        .vertex_attribute_descriptions(&attributes)
+        .vertex_binding_descriptions(&bindings);
+    let multisample_info =
+        vk::PipelineMultisampleStateCreateInfo::default().rasterization_samples(sample_count);
+
+    let color_attachment_formats = [format];
+    let mut rendering_info = vk::PipelineRenderingCreateInfo::default()
+        .color_attachment_formats(&color_attachment_formats);
+
+    let mut pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
+        .stages(&pipeline_shader_stages)
+        .vertex_input_state(&vertex_input_state)
+        .input_assembly_state(&input_assembly_info)
+        .viewport_state(&viewport_info)
+        .rasterization_state(&rasterization_info)
+        .multisample_state(&multisample_info)
+        .depth_stencil_state(&depth_stencil_info)
+        .color_blend_state(&color_blend_info)
+        .dynamic_state(&dynamic_state_info)
+        .layout(pipeline_layout)
+        .render_pass(render_pass)
+        .subpass(0);
+    if render_pass == vk::RenderPass::null() {
+        pipeline_create_info = pipeline_create_info.push_next(&mut rendering_info);
+    }
+
+    let pipeline = unsafe {
+        device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            std::slice::from_ref(&pipeline_create_info),
+            None,
+        )
+    };
+    unsafe {
+        device.destroy_shader_module(vertex_shader_module, None);
+        device.destroy_shader_module(fragment_shader_module, None);
+    }
+    Ok(pipeline.map_err(|(_, err)| err)?[0])
 }
@@ -15,7 +15,7 @@
 //!
 //! # Usage
 //!
-//! ```
+//! ```ignore
 //! fn main() -> Result<()> {
 //!     let event_loop = EventLoop::new();
 //!     // (1) Call Integration::<Arc<Mutex<Allocator>>>::new() in App::new().
@@ -48,17 +48,27 @@
 //!         }
 //!     })
 //! }
-//! // (5) Call integration.destroy() when drop app.
+//! // (5) Drop the integration before destroying the device when drop app.
 //! ```
 //!
 //! [Full example is in examples directory](https://github.com/MatchaChoco010/egui-winit-ash-integration/tree/main/examples)
 
 mod allocator;
+mod buffer;
+mod callback;
+mod descriptor;
+mod descriptor_pool;
+mod error;
 mod integration;
+mod user_texture;
 mod utils;
 
 pub use allocator::*;
+pub use callback::*;
+pub use descriptor::*;
+pub use error::*;
 pub use integration::*;
+pub use user_texture::*;
 
 #[cfg(feature = "gpu-allocator-feature")]
 mod gpu_allocator;
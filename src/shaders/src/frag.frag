@@ -7,6 +7,9 @@ layout(location = 0) out vec4 outColor;
 
 layout(binding = 0, set = 0) uniform sampler2D font_texture;
 
+// Set for sRGB targets, which expect linear colors and encode them on write
+layout(constant_id = 0) const bool LINEAR_OUTPUT = false;
+
 // 0-1 sRGB gamma  from  0-1 linear
 vec3 srgb_gamma_from_linear(vec3 rgb) {
     bvec3 cutoff = lessThan(rgb, vec3(0.0031308));
@@ -20,4 +23,16 @@ vec4 srgba_gamma_from_linear(vec4 rgba) {
     return vec4(srgb_gamma_from_linear(rgba.rgb), rgba.a);
 }
 
-void main() { outColor = inColor * srgba_gamma_from_linear(texture(font_texture, inUV)); }
+// 0-1 linear  from  0-1 sRGB gamma
+vec3 linear_from_srgb_gamma(vec3 rgb) {
+    bvec3 cutoff = lessThan(rgb, vec3(0.04045));
+    vec3 lower = rgb / vec3(12.92);
+    vec3 higher = pow((rgb + vec3(0.055)) / vec3(1.055), vec3(2.4));
+    return mix(higher, lower, vec3(cutoff));
+}
+
+void main() {
+    // egui colors are in gamma space
+    vec4 color = inColor * srgba_gamma_from_linear(texture(font_texture, inUV));
+    outColor = LINEAR_OUTPUT ? vec4(linear_from_srgb_gamma(color.rgb), color.a) : color;
+}
@@ -0,0 +1,215 @@
+#![warn(missing_docs)]
+
+use std::sync::{Arc, Mutex};
+
+use ash::vk;
+use egui::TextureId;
+
+use crate::{descriptor_pool::PooledDescriptorSet, *};
+
+/// A user texture that is unregistered when dropped.
+///
+/// Created by [`Integration::register_user_texture_handle`]. The texture is unregistered at the end of the next
+/// paint call after the handle is dropped, so that call still draws it, and its descriptor set is freed once the
+/// GPU has finished the frames using it.
+pub struct UserTextureHandle {
+    id: TextureId,
+    dropped: Arc<Mutex<Vec<TextureId>>>,
+}
+impl UserTextureHandle {
+    /// Get the id to draw the texture with, e.g. in `egui::Image`.
+    pub fn id(&self) -> TextureId {
+        self.id
+    }
+}
+impl Drop for UserTextureHandle {
+    fn drop(&mut self) {
+        if let Ok(mut dropped) = self.dropped.lock() {
+            dropped.push(self.id);
+        }
+    }
+}
+
+// A slot of a user texture; the generation is bumped on unregistration so old ids of the slot stay invalid.
+struct UserTextureSlot {
+    descriptor_set: Option<PooledDescriptorSet>,
+    generation: u32,
+}
+
+// The registered user textures, identified by `TextureId::User` with the slot index in the low 32 bits
+// and the generation of the slot in the high 32 bits.
+#[derive(Default)]
+pub(crate) struct UserTextures {
+    slots: Vec<UserTextureSlot>,
+    // ids of dropped handles, unregistered at the end of the next paint call
+    dropped: Arc<Mutex<Vec<TextureId>>>,
+    // descriptor sets of unregistered textures, retired with the frame of the next paint call
+    unregistered: Vec<PooledDescriptorSet>,
+}
+impl UserTextures {
+    pub(crate) fn insert(&mut self, descriptor_set: PooledDescriptorSet) -> TextureId {
+        let index = match self
+            .slots
+            .iter()
+            .position(|slot| slot.descriptor_set.is_none())
+        {
+            Some(index) => index,
+            None => {
+                self.slots.push(UserTextureSlot {
+                    descriptor_set: None,
+                    generation: 0,
+                });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.descriptor_set = Some(descriptor_set);
+        TextureId::User(((slot.generation as u64) << 32) | index as u64)
+    }
+
+    pub(crate) fn get(&self, texture_id: TextureId) -> Option<vk::DescriptorSet> {
+        self.get_pooled(texture_id)
+            .map(|descriptor_set| descriptor_set.set)
+    }
+
+    // Free the slot of a texture; its descriptor set is kept until the GPU has finished with it.
+    pub(crate) fn remove(&mut self, texture_id: TextureId) -> Result<(), IntegrationError> {
+        let TextureId::User(id) = texture_id else {
+            return Err(IntegrationError::Misuse(
+                "The internal texture cannot be unregistered; please pass the texture ID of UserTexture.",
+            ));
+        };
+        let descriptor_set = self.get_pooled(texture_id).ok_or(IntegrationError::Misuse(
+            "This UserTexture has already been unregistered",
+        ))?;
+        let slot = &mut self.slots[id as u32 as usize];
+        slot.descriptor_set = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.unregistered.push(descriptor_set);
+        Ok(())
+    }
+
+    pub(crate) fn handle(&self, texture_id: TextureId) -> UserTextureHandle {
+        UserTextureHandle {
+            id: texture_id,
+            dropped: Arc::clone(&self.dropped),
+        }
+    }
+
+    pub(crate) fn take_dropped(&mut self) -> Vec<TextureId> {
+        self.dropped
+            .lock()
+            .map(|mut dropped| std::mem::take(&mut *dropped))
+            .unwrap_or_default()
+    }
+
+    // Unregister the textures of dropped handles; ids unregistered manually meanwhile are ignored.
+    pub(crate) fn unregister_dropped(&mut self) {
+        for texture_id in self.take_dropped() {
+            self.remove(texture_id).ok();
+        }
+    }
+
+    pub(crate) fn take_unregistered(&mut self) -> Vec<PooledDescriptorSet> {
+        std::mem::take(&mut self.unregistered)
+    }
+
+    fn get_pooled(&self, texture_id: TextureId) -> Option<PooledDescriptorSet> {
+        let TextureId::User(id) = texture_id else {
+            return None;
+        };
+        self.slots
+            .get(id as u32 as usize)
+            .filter(|slot| slot.generation == (id >> 32) as u32)
+            .and_then(|slot| slot.descriptor_set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ash::vk::Handle;
+
+    use super::*;
+
+    fn descriptor_set(raw: u64) -> PooledDescriptorSet {
+        PooledDescriptorSet {
+            set: vk::DescriptorSet::from_raw(raw),
+            pool: 0,
+        }
+    }
+
+    #[test]
+    fn reinsert_reuses_slot_with_new_generation() {
+        let mut user_textures = UserTextures::default();
+        let first = user_textures.insert(descriptor_set(1));
+        user_textures.remove(first).unwrap();
+        let second = user_textures.insert(descriptor_set(2));
+
+        assert_eq!(user_textures.slots.len(), 1);
+        assert_ne!(first, second);
+        assert_eq!(user_textures.get(first), None);
+        assert_eq!(
+            user_textures.get(second),
+            Some(vk::DescriptorSet::from_raw(2))
+        );
+    }
+
+    #[test]
+    fn remove_rejects_stale_and_managed_ids() {
+        let mut user_textures = UserTextures::default();
+        let id = user_textures.insert(descriptor_set(1));
+        user_textures.remove(id).unwrap();
+
+        assert!(matches!(
+            user_textures.remove(id),
+            Err(IntegrationError::Misuse(_))
+        ));
+        user_textures.insert(descriptor_set(2));
+        assert!(matches!(
+            user_textures.remove(id),
+            Err(IntegrationError::Misuse(_))
+        ));
+        assert!(matches!(
+            user_textures.remove(TextureId::Managed(0)),
+            Err(IntegrationError::Misuse(_))
+        ));
+    }
+
+    #[test]
+    fn removed_sets_are_kept_until_taken() {
+        let mut user_textures = UserTextures::default();
+        let id = user_textures.insert(descriptor_set(1));
+        user_textures.remove(id).unwrap();
+
+        let unregistered = user_textures.take_unregistered();
+        assert_eq!(unregistered.len(), 1);
+        assert_eq!(unregistered[0].set, vk::DescriptorSet::from_raw(1));
+        assert!(user_textures.take_unregistered().is_empty());
+    }
+
+    #[test]
+    fn dropped_handles_are_reported_once() {
+        let mut user_textures = UserTextures::default();
+        let id = user_textures.insert(descriptor_set(1));
+        drop(user_textures.handle(id));
+
+        assert_eq!(user_textures.take_dropped(), vec![id]);
+        assert!(user_textures.take_dropped().is_empty());
+    }
+
+    #[test]
+    fn dropped_handles_stay_drawable_until_unregistered() {
+        let mut user_textures = UserTextures::default();
+        let id = user_textures.insert(descriptor_set(1));
+        let other = user_textures.insert(descriptor_set(2));
+        drop(user_textures.handle(id));
+        drop(user_textures.handle(other));
+        user_textures.remove(other).unwrap();
+
+        // A frame built before the drop is painted with the texture
+        assert_eq!(user_textures.get(id), Some(vk::DescriptorSet::from_raw(1)));
+        user_textures.unregister_dropped();
+        assert_eq!(user_textures.get(id), None);
+        assert_eq!(user_textures.take_unregistered().len(), 2);
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::too_many_arguments)]
 pub fn insert_image_memory_barrier(
     device: &ash::Device,
     cmd_buff: &ash::vk::CommandBuffer,
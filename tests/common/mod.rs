@@ -0,0 +1,371 @@
+//! Headless rendering through `Integration` and comparison against golden images.
+//!
+//! Golden images live in `tests/golden`. A missing golden image fails the test; all of them are written
+//! when `UPDATE_GOLDEN` is set, review the written images before committing them.
+//!
+//! The tests need a Vulkan device such as lavapipe and are ignored by default; run them with
+//! `cargo test --test golden -- --ignored`, which fails if there is no device.
+
+use std::{
+    ffi::CString,
+    mem::ManuallyDrop,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use ash::{vk, Device, Entry, Instance};
+use egui_winit_ash_integration::{IntegrationDescriptor, RenderingMode};
+use gpu_allocator::vulkan::{Allocator, AllocatorCreateDesc};
+
+// Frames rendered per image, so that layouts measured in the first frame have settled.
+const FRAMES: usize = 2;
+
+/// The image and rendering mode a test renders into.
+#[derive(Clone, Copy)]
+pub struct Target {
+    pub format: vk::Format,
+    pub rendering_mode: RenderingMode,
+}
+impl Default for Target {
+    // UNORM keeps the bytes egui outputs without sRGB conversions.
+    fn default() -> Self {
+        Self {
+            format: vk::Format::R8G8B8A8_UNORM,
+            rendering_mode: RenderingMode::RenderPass,
+        }
+    }
+}
+
+// A Vulkan device without a surface, preferring a software implementation such as lavapipe.
+struct Headless {
+    _entry: Entry,
+    instance: Instance,
+    physical_device: vk::PhysicalDevice,
+    device: Device,
+    qfi: u32,
+    queue: vk::Queue,
+    dynamic_rendering: bool,
+    allocator: ManuallyDrop<Arc<Mutex<Allocator>>>,
+}
+impl Headless {
+    // Create the device, or return `None` if there is no Vulkan implementation to run on.
+    fn new() -> Option<Self> {
+        let entry = Entry::linked();
+        let app_name = CString::new("egui-winit-ash-integration tests").unwrap();
+        let app_info = vk::ApplicationInfo::default()
+            .application_name(&app_name)
+            .api_version(vk::make_api_version(0, 1, 3, 0));
+        let instance = unsafe {
+            entry.create_instance(
+                &vk::InstanceCreateInfo::default().application_info(&app_info),
+                None,
+            )
+        }
+        .ok()?;
+
+        // Find a device with a graphics queue
+        let devices = unsafe { instance.enumerate_physical_devices() }.unwrap_or_default();
+        let mut candidates = devices
+            .into_iter()
+            .filter_map(|physical_device| {
+                let qfi = unsafe {
+                    instance.get_physical_device_queue_family_properties(physical_device)
+                }
+                .iter()
+                .position(|properties| properties.queue_flags.contains(vk::QueueFlags::GRAPHICS))?;
+                Some((physical_device, qfi as u32))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|&(physical_device, _)| {
+            let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+            properties.device_type != vk::PhysicalDeviceType::CPU
+        });
+        let Some(&(physical_device, qfi)) = candidates.first() else {
+            unsafe {
+                instance.destroy_instance(None);
+            }
+            return None;
+        };
+
+        // Enable dynamic rendering where the device supports it
+        let api_version =
+            unsafe { instance.get_physical_device_properties(physical_device) }.api_version;
+        let dynamic_rendering = api_version >= vk::make_api_version(0, 1, 3, 0) && {
+            let mut features13 = vk::PhysicalDeviceVulkan13Features::default();
+            let mut features = vk::PhysicalDeviceFeatures2::default().push_next(&mut features13);
+            unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+            features13.dynamic_rendering == vk::TRUE
+        };
+        let mut features13 =
+            vk::PhysicalDeviceVulkan13Features::default().dynamic_rendering(dynamic_rendering);
+        let queue_create_infos = [vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(qfi)
+            .queue_priorities(&[1.0])];
+        let mut device_create_info =
+            vk::DeviceCreateInfo::default().queue_create_infos(&queue_create_infos);
+        if dynamic_rendering {
+            device_create_info = device_create_info.push_next(&mut features13);
+        }
+        let device = unsafe { instance.create_device(physical_device, &device_create_info, None) }
+            .expect("Failed to create device.");
+        let queue = unsafe { device.get_device_queue(qfi, 0) };
+        let allocator = Allocator::new(&AllocatorCreateDesc {
+            instance: instance.clone(),
+            device: device.clone(),
+            physical_device,
+            debug_settings: Default::default(),
+            buffer_device_address: false,
+            allocation_sizes: Default::default(),
+        })
+        .expect("Failed to create allocator.");
+
+        Some(Self {
+            _entry: entry,
+            instance,
+            physical_device,
+            device,
+            qfi,
+            queue,
+            dynamic_rendering,
+            allocator: ManuallyDrop::new(Arc::new(Mutex::new(allocator))),
+        })
+    }
+}
+impl Drop for Headless {
+    fn drop(&mut self) {
+        unsafe {
+            // The allocator frees its memory blocks with the device
+            ManuallyDrop::drop(&mut self.allocator);
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+/// Render the ui built by `run_ui` into a `width` x `height` image of the default target.
+pub fn render(
+    width: u32,
+    height: u32,
+    pixels_per_point: f32,
+    run_ui: impl FnMut(&egui::Context),
+) -> egui::ColorImage {
+    render_to(Target::default(), width, height, pixels_per_point, run_ui)
+}
+
+/// Render the ui built by `run_ui` into a `width` x `height` image of `target`.
+///
+/// Panics if no Vulkan device is available.
+pub fn render_to(
+    target: Target,
+    width: u32,
+    height: u32,
+    pixels_per_point: f32,
+    mut run_ui: impl FnMut(&egui::Context),
+) -> egui::ColorImage {
+    let headless = Headless::new().expect("No Vulkan device available.");
+    if target.rendering_mode == RenderingMode::DynamicRendering {
+        assert!(
+            headless.dynamic_rendering,
+            "The Vulkan device does not support dynamic rendering."
+        );
+    }
+    let max_texture_side = unsafe {
+        headless
+            .instance
+            .get_physical_device_properties(headless.physical_device)
+    }
+    .limits
+    .max_image_dimension2_d as usize;
+    let mut integration = IntegrationDescriptor::new(
+        width,
+        height,
+        pixels_per_point as f64,
+        headless.device.clone(),
+        Arc::clone(&headless.allocator),
+        headless.qfi,
+        headless.queue,
+    )
+    .headless(target.format)
+    .max_texture_side(max_texture_side)
+    .rendering_mode(target.rendering_mode)
+    .build()
+    .expect("Failed to create integration.");
+
+    let device = &headless.device;
+    let command_pool = unsafe {
+        device.create_command_pool(
+            &vk::CommandPoolCreateInfo::default()
+                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                .queue_family_index(headless.qfi),
+            None,
+        )
+    }
+    .expect("Failed to create command pool.");
+    let command_buffer = unsafe {
+        device.allocate_command_buffers(
+            &vk::CommandBufferAllocateInfo::default()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1),
+        )
+    }
+    .expect("Failed to allocate command buffers.")[0];
+    let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) }
+        .expect("Failed to create fence.");
+
+    for _ in 0..FRAMES {
+        integration
+            .begin_frame_with_input(egui::RawInput::default())
+            .expect("Failed to begin frame.");
+        run_ui(&integration.context());
+        let output = integration.end_frame_headless();
+        let clipped_meshes = integration
+            .context()
+            .tessellate(output.shapes, output.pixels_per_point);
+
+        unsafe {
+            device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo::default()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .expect("Failed to begin command buffer.");
+        }
+        integration
+            .paint(command_buffer, 0, clipped_meshes, output.textures_delta)
+            .expect("Failed to paint.");
+        unsafe {
+            device
+                .end_command_buffer(command_buffer)
+                .expect("Failed to end command buffer.");
+            device
+                .queue_submit(
+                    headless.queue,
+                    &[vk::SubmitInfo::default().command_buffers(&[command_buffer])],
+                    fence,
+                )
+                .expect("Failed to submit.");
+            device
+                .wait_for_fences(&[fence], true, u64::MAX)
+                .expect("Failed to wait for fence.");
+            device
+                .reset_fences(&[fence])
+                .expect("Failed to reset fence.");
+        }
+    }
+
+    let (image, _) = integration.offscreen_image().unwrap();
+    let color_image = integration
+        .read_image(
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            target.format,
+            vk::Extent2D { width, height },
+        )
+        .expect("Failed to read image.");
+
+    unsafe {
+        device.destroy_fence(fence, None);
+        device.destroy_command_pool(command_pool, None);
+    }
+    drop(integration);
+    color_image
+}
+
+/// Compare `image` with the golden image `tests/golden/<name>.png`.
+///
+/// Each channel of each pixel may differ by up to `tolerance`. On a mismatch the rendered image and an image
+/// marking the differing pixels in red are written to the test's temporary directory before panicking.
+/// With `UPDATE_GOLDEN` set, the golden image is written instead.
+pub fn assert_golden(name: &str, image: &egui::ColorImage, tolerance: u8) {
+    let golden_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{name}.png"));
+    let rendered = to_rgba_image(image);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_path.parent().unwrap())
+            .expect("Failed to create golden image directory.");
+        rendered
+            .save(&golden_path)
+            .expect("Failed to write golden image.");
+        eprintln!("Wrote golden image {}", golden_path.display());
+        return;
+    }
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    let actual_path = out_dir.join(format!("{name}.actual.png"));
+    if !golden_path.exists() {
+        std::fs::create_dir_all(&out_dir).expect("Failed to create output directory.");
+        rendered
+            .save(&actual_path)
+            .expect("Failed to write rendered image.");
+        panic!(
+            "{name}: the golden image {} is missing; see {} or run with UPDATE_GOLDEN set",
+            golden_path.display(),
+            actual_path.display()
+        );
+    }
+
+    let golden = image::open(&golden_path)
+        .expect("Failed to read golden image.")
+        .to_rgba8();
+    assert_eq!(
+        golden.dimensions(),
+        rendered.dimensions(),
+        "{name}: the size differs from the golden image"
+    );
+
+    let mut mismatches = 0;
+    let diff = image::RgbaImage::from_fn(rendered.width(), rendered.height(), |x, y| {
+        let expected = golden.get_pixel(x, y);
+        let actual = rendered.get_pixel(x, y);
+        let differs = expected
+            .0
+            .iter()
+            .zip(actual.0.iter())
+            .any(|(&e, &a)| e.abs_diff(a) > tolerance);
+        if differs {
+            mismatches += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            // Dimmed golden pixel for context
+            let [r, g, b, _] = expected.0;
+            let luma = ((r as u32 + g as u32 + b as u32) / 3 / 3) as u8;
+            image::Rgba([luma, luma, luma, 255])
+        }
+    });
+    if mismatches > 0 {
+        std::fs::create_dir_all(&out_dir).expect("Failed to create output directory.");
+        let diff_path = out_dir.join(format!("{name}.diff.png"));
+        rendered
+            .save(&actual_path)
+            .expect("Failed to write rendered image.");
+        diff.save(&diff_path).expect("Failed to write diff image.");
+        panic!(
+            "{name}: {mismatches} pixels differ from {} by more than {tolerance}; see {} and {}",
+            golden_path.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+/// Assert that every pixel of `image` is opaque, for images covered by an opaque egui panel.
+pub fn assert_opaque(name: &str, image: &egui::ColorImage) {
+    let translucent = image.pixels.iter().filter(|pixel| pixel.a() != 255).count();
+    assert_eq!(
+        translucent, 0,
+        "{name}: {translucent} pixels under an opaque panel are not opaque"
+    );
+}
+
+fn to_rgba_image(image: &egui::ColorImage) -> image::RgbaImage {
+    let [width, height] = image.size;
+    let bytes = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_array())
+        .collect();
+    image::RgbaImage::from_raw(width as u32, height as u32, bytes).unwrap()
+}
@@ -0,0 +1,129 @@
+//! Golden image tests of what `Integration::paint` draws, run headlessly on any Vulkan device.
+
+mod common;
+
+use ash::vk;
+use common::{assert_golden, assert_opaque, render, render_to, Target};
+use egui_winit_ash_integration::RenderingMode;
+
+// Allowed difference per channel, covering rasterization and blending differences between drivers.
+const TOLERANCE: u8 = 8;
+
+fn widgets_ui(ctx: &egui::Context) {
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.heading("Heading");
+        ui.label("Some text in a label.");
+        let _ = ui.button("Button");
+        ui.checkbox(&mut true, "Checkbox");
+        ui.add(egui::Slider::new(&mut 0.5, 0.0..=1.0));
+    });
+}
+
+fn translucent_ui(ctx: &egui::Context) {
+    egui::CentralPanel::default()
+        .frame(egui::Frame::none().fill(egui::Color32::WHITE))
+        .show(ctx, |ui| {
+            let painter = ui.painter();
+            painter.rect_filled(
+                egui::Rect::from_min_size(egui::pos2(16.0, 16.0), egui::vec2(64.0, 64.0)),
+                0.0,
+                egui::Color32::from_rgba_unmultiplied(255, 0, 0, 128),
+            );
+            painter.rect_filled(
+                egui::Rect::from_min_size(egui::pos2(48.0, 48.0), egui::vec2(64.0, 64.0)),
+                8.0,
+                egui::Color32::from_rgba_unmultiplied(0, 0, 255, 96),
+            );
+            painter.circle_filled(
+                egui::pos2(64.0, 64.0),
+                24.0,
+                egui::Color32::from_black_alpha(64),
+            );
+        });
+}
+
+#[test]
+#[ignore = "needs a Vulkan device"]
+fn widgets() {
+    let image = render(320, 240, 1.0, widgets_ui);
+    assert_opaque("widgets", &image);
+    assert_golden("widgets", &image, TOLERANCE);
+}
+
+#[test]
+#[ignore = "needs a Vulkan device"]
+fn translucent_blending() {
+    let image = render(128, 128, 1.0, translucent_ui);
+    assert_opaque("translucent_blending", &image);
+    assert_golden("translucent_blending", &image, TOLERANCE);
+}
+
+#[test]
+#[ignore = "needs a Vulkan device"]
+fn clipped_scroll_area() {
+    let image = render(200, 160, 1.0, |ctx| {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(80.0)
+                .show(ui, |ui| {
+                    for i in 0..20 {
+                        ui.label(format!("Clipped row {i}"));
+                    }
+                });
+            ui.separator();
+            ui.label("Below the scroll area");
+        });
+    });
+    assert_golden("clipped_scroll_area", &image, TOLERANCE);
+}
+
+#[test]
+#[ignore = "needs a Vulkan device"]
+fn high_dpi() {
+    let image = render(320, 240, 2.0, |ctx| {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.label("Rendered at two pixels per point.");
+            let _ = ui.button("Button");
+        });
+    });
+    assert_golden("high_dpi", &image, TOLERANCE);
+}
+
+// The shader outputs linear colors to sRGB targets, which blend in linear space.
+#[test]
+#[ignore = "needs a Vulkan device"]
+fn srgb_target() {
+    let target = Target {
+        format: vk::Format::R8G8B8A8_SRGB,
+        ..Target::default()
+    };
+    let image = render_to(target, 320, 240, 1.0, |ctx| {
+        widgets_ui(ctx);
+        egui::Area::new("translucent".into())
+            .fixed_pos(egui::pos2(120.0, 40.0))
+            .show(ctx, |ui| {
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::vec2(96.0, 96.0), egui::Sense::hover());
+                ui.painter().rect_filled(
+                    rect,
+                    8.0,
+                    egui::Color32::from_rgba_unmultiplied(0, 0, 255, 96),
+                );
+            });
+    });
+    assert_opaque("srgb_target", &image);
+    assert_golden("srgb_target", &image, TOLERANCE);
+}
+
+// Dynamic rendering draws the same as a render pass.
+#[test]
+#[ignore = "needs a Vulkan device"]
+fn dynamic_rendering() {
+    let target = Target {
+        rendering_mode: RenderingMode::DynamicRendering,
+        ..Target::default()
+    };
+    let image = render_to(target, 128, 128, 1.0, translucent_ui);
+    assert_opaque("dynamic_rendering", &image);
+    assert_golden("translucent_blending", &image, TOLERANCE);
+}